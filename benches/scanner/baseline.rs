@@ -0,0 +1,552 @@
+//! The scanner as it was before it borrowed from the source and produced tokens lazily,
+//! kept as it was to compare against. Every lexeme is copied into its own `String` and the
+//! whole token vector is cloned at the end.
+#![allow(dead_code)]
+
+use core::{cmp::PartialEq, prelude::v1::derive};
+
+use phf::phf_map;
+use rlox::diagnostics::Diagnostics;
+use rlox::span::Span;
+use unicode_normalization::UnicodeNormalization;
+use TokenType::*;
+
+static KEYWORDS: phf::Map<&'static str, TokenType> = phf_map! {
+    "and" => And,
+    "class" => Class,
+    "else" => Else,
+    "false" => False,
+    "for" => For,
+    "fun" => Fun,
+    "if" => If,
+    "nil" => Nil,
+    "or" => Or,
+    "print" => Print,
+    "return" => Return,
+    "super" => Super,
+    "this" => This,
+    "true"=> True,
+    "var" => Var,
+    "while" => While
+};
+
+/// Scans UTF-8 source text, all positions are byte offsets into `source`.
+pub struct Scanner<'a> {
+    source: &'a str,
+    start: usize,
+    current: usize,
+    line: usize,
+    // where the current line begins, to derive columns from
+    line_start: usize,
+    // position of the token currently being scanned, tokens may span several lines
+    start_line: usize,
+    start_column: usize,
+    result: Vec<Token>,
+    // one entry per string interpolation being scanned, counting the braces opened inside it
+    interpolations: Vec<usize>,
+    diagnostics: &'a mut Diagnostics,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(source: &'a str, diagnostics: &'a mut Diagnostics) -> Self {
+        Scanner {
+            source,
+            start: 0,
+            current: 0,
+            line: 1,
+            line_start: 0,
+            start_line: 1,
+            start_column: 1,
+            result: vec![],
+            interpolations: vec![],
+            diagnostics,
+        }
+    }
+
+    pub fn scan_tokens(&mut self) -> Vec<Token> {
+        while !self.at_end() {
+            self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column(self.start);
+            let char = self.advance();
+            match char {
+                '(' => self.append_token(LeftParen),
+                ')' => self.append_token(RightParen),
+                '{' => {
+                    if let Some(depth) = self.interpolations.last_mut() {
+                        *depth += 1;
+                    }
+                    self.append_token(LeftBrace);
+                }
+                '}' => match self.interpolations.last_mut() {
+                    // closes the embedded expression, the string continues after it
+                    Some(0) => {
+                        self.interpolations.pop();
+                        self.consume_string();
+                    }
+                    Some(depth) => {
+                        *depth -= 1;
+                        self.append_token(RightBrace);
+                    }
+                    None => self.append_token(RightBrace),
+                },
+                ',' => self.append_token(Comma),
+                '.' => self.append_token(Dot),
+                '-' => self.append_token(Minus),
+                '+' => self.append_token(Plus),
+                ';' => self.append_token(Semicolon),
+                '*' => self.append_token(Star),
+                '!' => {
+                    if self.next_is('=') {
+                        self.append_token(BangEqual);
+                    } else {
+                        self.append_token(Bang);
+                    }
+                }
+                '=' => {
+                    if self.next_is('=') {
+                        self.append_token(EqualEqual);
+                    } else {
+                        self.append_token(Equal);
+                    }
+                }
+                '<' => {
+                    if self.next_is('=') {
+                        self.append_token(LessEqual);
+                    } else {
+                        self.append_token(Less);
+                    }
+                }
+                '>' => {
+                    if self.next_is('=') {
+                        self.append_token(GreaterEqual);
+                    } else {
+                        self.append_token(Greater);
+                    }
+                }
+                '/' => {
+                    if self.next_is('/') {
+                        while self.peek() != '\n' && !self.at_end() {
+                            // keep eating character until the end of the line
+                            self.advance();
+                        }
+                    } else if self.next_is('*') {
+                        self.consume_block_comment();
+                    } else {
+                        self.append_token(Slash);
+                    }
+                }
+                '0'..='9' => self.consume_number(),
+                '"' => self.consume_string(),
+                'r' if self.peek() == '"' => {
+                    self.advance();
+                    self.consume_raw_string();
+                }
+                '\n' => self.newline(),
+                '\t' | ' ' => {}
+                c => {
+                    if self.is_alpha(c) {
+                        self.consume_identifier();
+                    } else {
+                        let message = format!("Unexpected character: {}", c);
+                        self.error(&message);
+                    }
+                }
+            }
+        }
+
+        self.result.push(Token {
+            token_type: TokenType::Eof,
+            lexeme: String::from(""),
+            literal: LiteralType::Nil,
+            line: self.line,
+            column: self.column(self.current),
+            span: Span::new(self.current, self.current),
+        });
+
+        self.result.clone()
+    }
+
+    /// Call after consuming a line break
+    fn newline(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
+    }
+
+    fn column(&self, offset: usize) -> usize {
+        self.source[self.line_start..offset].chars().count() + 1
+    }
+
+    /// Reports an error spanning the token currently being scanned
+    fn error(&mut self, message: &str) {
+        let span = Span::new(self.start, self.current);
+        self.diagnostics
+            .error(span, self.start_line, self.start_column, message);
+    }
+
+    /// Reports an error spanning from `start` to the current position on the current line
+    fn error_from(&mut self, start: usize, message: &str) {
+        let span = Span::new(start, self.current);
+        self.diagnostics
+            .error(span, self.line, self.column(start), message);
+    }
+
+    fn next_is(&mut self, c: char) -> bool {
+        if self.at_end() || self.peek() != c {
+            false
+        } else {
+            self.current += c.len_utf8();
+            true
+        }
+    }
+
+    fn peek(&self) -> char {
+        self.source[self.current..].chars().next().unwrap_or('\0')
+    }
+
+    fn peek_next(&self) -> char {
+        self.source[self.current..].chars().nth(1).unwrap_or('\0')
+    }
+
+    fn advance(&mut self) -> char {
+        let result = self.peek();
+        self.current += result.len_utf8();
+        result
+    }
+
+    fn at_end(&self) -> bool {
+        self.current >= self.source.len()
+    }
+
+    /// Block comments nest, so commenting out code that contains one keeps working
+    fn consume_block_comment(&mut self) {
+        let mut depth = 1;
+        while depth > 0 {
+            if self.at_end() {
+                // reported where the comment was opened, the end of the file is no help
+                self.error("Unterminated block comment.");
+                return;
+            }
+            match self.advance() {
+                '/' if self.next_is('*') => depth += 1,
+                '*' if self.next_is('/') => depth -= 1,
+                '\n' => self.newline(),
+                _ => {}
+            }
+        }
+    }
+
+    fn consume_string(&mut self) {
+        let mut value = String::new();
+        loop {
+            if self.at_end() {
+                self.error("Unterminated string.");
+                return;
+            }
+            match self.advance() {
+                '"' => break,
+                '$' if self.next_is('{') => {
+                    self.interpolations.push(0);
+                    let literal = LiteralType::StringLiteral(value);
+                    self.append_token_literal(Interpolation, literal);
+                    return;
+                }
+                '\\' => {
+                    if let Some(c) = self.consume_escape() {
+                        value.push(c);
+                    }
+                }
+                c => {
+                    if c == '\n' {
+                        self.newline();
+                    }
+                    value.push(c);
+                }
+            }
+        }
+
+        self.append_token_literal(TString, LiteralType::StringLiteral(value));
+    }
+
+    /// Call after consuming the backslash, returns `None` for invalid escapes after reporting them
+    fn consume_escape(&mut self) -> Option<char> {
+        let start = self.current - 1;
+        if self.at_end() {
+            return None;
+        }
+        let c = match self.advance() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '\\' => '\\',
+            '"' => '"',
+            '0' => '\0',
+            '$' => '$',
+            'u' => return self.consume_unicode_escape(start),
+            '\n' => {
+                self.error_from(start, "Invalid escape sequence.");
+                self.newline();
+                return None;
+            }
+            _ => {
+                self.error_from(start, "Invalid escape sequence.");
+                return None;
+            }
+        };
+        Some(c)
+    }
+
+    /// Reads the `{XXXX}` part of a `\u{XXXX}` escape, with 1 to 6 hex digits
+    fn consume_unicode_escape(&mut self, start: usize) -> Option<char> {
+        if !self.next_is('{') {
+            self.error_from(start, "Invalid unicode escape.");
+            return None;
+        }
+        let digits_start = self.current;
+        while self.peek().is_ascii_hexdigit() {
+            self.advance();
+        }
+        let digits = &self.source[digits_start..self.current];
+        if !self.next_is('}') || digits.is_empty() || digits.len() > 6 {
+            self.error_from(start, "Invalid unicode escape.");
+            return None;
+        }
+        let c = u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32);
+        if c.is_none() {
+            self.error_from(start, "Invalid unicode escape.");
+        }
+        c
+    }
+
+    /// `r"..."` strings take their content as it is written, backslashes included
+    fn consume_raw_string(&mut self) {
+        while self.peek() != '"' && !self.at_end() {
+            if self.advance() == '\n' {
+                self.newline();
+            }
+        }
+
+        if self.at_end() {
+            self.error("Unterminated string.");
+            return;
+        }
+
+        self.advance();
+        let value = &self.source[self.start + 2..self.current - 1];
+        self.append_token_literal(TString, LiteralType::StringLiteral(String::from(value)));
+    }
+
+    fn consume_number(&mut self) {
+        if &self.source[self.start..self.current] == "0" {
+            let radix = match self.peek() {
+                'x' => Some(16),
+                'o' => Some(8),
+                'b' => Some(2),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                self.advance();
+                self.consume_radix_number(radix);
+                return;
+            }
+        }
+
+        self.consume_digits();
+        if self.peek() == '.' && self.is_digit(self.peek_next()) {
+            //consume the .
+            self.advance();
+            self.consume_digits();
+        }
+        if matches!(self.peek(), 'e' | 'E') {
+            self.advance();
+            if matches!(self.peek(), '+' | '-') {
+                self.advance();
+            }
+            if !self.is_digit(self.peek()) {
+                self.error("Expect digits in the exponent.");
+                return;
+            }
+            self.consume_digits();
+        }
+
+        let value = &self.source[self.start..self.current];
+        if !separators_between_digits(value, |c| c.is_ascii_digit()) {
+            self.error("Digit separators are only allowed between digits.");
+            return;
+        }
+        match value.replace('_', "").parse::<f64>() {
+            Ok(number) => self.append_token_literal(Number, LiteralType::NumberLiteral(number)),
+            Err(_) => self.error("Invalid number literal."),
+        }
+    }
+
+    /// Call after consuming a `0x`, `0o` or `0b` prefix
+    fn consume_radix_number(&mut self, radix: u32) {
+        // take everything that could belong to the literal, so bad digits are reported as part of it
+        while self.is_alpha_numeric(self.peek()) {
+            self.advance();
+        }
+
+        let digits = &self.source[self.start + 2..self.current];
+        let valid = separators_between_digits(digits, |c| c.is_digit(radix))
+            && !digits.is_empty()
+            && digits.chars().all(|c| c == '_' || c.is_digit(radix));
+        let number = u64::from_str_radix(&digits.replace('_', ""), radix);
+        match number {
+            Ok(number) if valid => {
+                self.append_token_literal(Number, LiteralType::NumberLiteral(number as f64))
+            }
+            _ => {
+                let kind = match radix {
+                    16 => "hexadecimal",
+                    8 => "octal",
+                    _ => "binary",
+                };
+                self.error(&format!("Invalid {} literal.", kind));
+            }
+        }
+    }
+
+    /// Digits may be grouped with `_`, as in `1_000_000`
+    fn consume_digits(&mut self) {
+        while self.is_digit(self.peek()) || self.peek() == '_' {
+            self.advance();
+        }
+    }
+
+    fn consume_identifier(&mut self) {
+        while self.is_alpha_numeric(self.peek()) {
+            self.advance();
+        }
+
+        // keywords have to be spelled exactly, only user identifiers are normalized
+        let value = &self.source[self.start..self.current];
+        match KEYWORDS.get(value) {
+            Some(keyword) => self.append_token(keyword.clone()),
+            None => {
+                // so that visually identical names written differently refer to the same variable
+                let name: String = value.nfc().collect();
+                self.push_token(Identifier, name, LiteralType::Nil);
+            }
+        }
+    }
+
+    fn is_digit(&self, c: char) -> bool {
+        c.is_ascii_digit()
+    }
+
+    /// Identifiers follow UAX #31, with `_` allowed at the start as well
+    fn is_alpha(&self, c: char) -> bool {
+        c == '_' || unicode_ident::is_xid_start(c)
+    }
+
+    fn is_alpha_numeric(&self, c: char) -> bool {
+        unicode_ident::is_xid_continue(c)
+    }
+
+    fn append_token(&mut self, token_type: TokenType) {
+        self.append_token_literal(token_type, LiteralType::Nil);
+    }
+
+    fn append_token_literal(&mut self, token_type: TokenType, literal: LiteralType) {
+        let lexeme = String::from(&self.source[self.start..self.current]);
+        self.push_token(token_type, lexeme, literal);
+    }
+
+    fn push_token(&mut self, token_type: TokenType, lexeme: String, literal: LiteralType) {
+        let token = Token {
+            token_type,
+            lexeme,
+            literal,
+            line: self.start_line,
+            column: self.start_column,
+            span: Span::new(self.start, self.current),
+        };
+        self.result.push(token);
+    }
+}
+
+/// Separators have to sit between two digits, possibly next to further separators.
+fn separators_between_digits(text: &str, is_digit: impl Fn(char) -> bool) -> bool {
+    let chars: Vec<char> = text.chars().collect();
+    let digit_or_separator = |i: Option<usize>| {
+        i.and_then(|i| chars.get(i))
+            .is_some_and(|&c| c == '_' || is_digit(c))
+    };
+    (0..chars.len())
+        .filter(|&i| chars[i] == '_')
+        .all(|i| digit_or_separator(i.checked_sub(1)) && digit_or_separator(Some(i + 1)))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub token_type: TokenType,
+    pub lexeme: String,
+    pub literal: LiteralType,
+    /// 1-based line and column of the first character of the token
+    pub line: usize,
+    pub column: usize,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenType {
+    // Single-character tokens.
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    Comma,
+    Dot,
+    Minus,
+    Plus,
+    Semicolon,
+    Slash,
+    Star,
+
+    // One or two character tokens.
+    Bang,
+    BangEqual,
+    Equal,
+    EqualEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+
+    // Literals.
+    Identifier,
+    TString,
+    /// The part of a string literal up to a `${`, the embedded expression follows
+    Interpolation,
+    Number,
+
+    // Keywords.
+    And,
+    Class,
+    Else,
+    False,
+    Fun,
+    For,
+    If,
+    Nil,
+    Or,
+    Print,
+    Return,
+    Super,
+    This,
+    True,
+    Var,
+    While,
+
+    Eof,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiteralType {
+    Nil,
+    StringLiteral(String),
+    NumberLiteral(f64),
+}
@@ -0,0 +1,89 @@
+//! Compares the streaming scanner with the one it replaced, on a generated multi-megabyte
+//! script, and on the same script minified to a single line. Run with
+//! `cargo bench --bench scanner`.
+
+mod baseline;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rlox::diagnostics::Diagnostics;
+use rlox::scanner::Scanner;
+
+const SNIPPET: &str = r#"
+// generated code, one class and a few functions per chunk
+class Point {
+    init(x, y) { this.x = x; this.y = y; }
+    length() { return sqrt(this.x * this.x + this.y * this.y); }
+}
+
+fun scale(point, factor) {
+    /* keep the original around */
+    var copy = Point(point.x * factor, point.y * factor);
+    if (copy.length() >= 1000.5) { print "too long: ${copy.length()}"; }
+    return copy;
+}
+
+var größe = 42;
+var name = "Grüße aus Köln";
+for (var i = 0; i < 100; i = i + 1) { größe = größe + scale(Point(i, 2), 0.5).x; }
+"#;
+
+const TARGET_SIZE: usize = 4 * 1024 * 1024;
+const RUNS: usize = 10;
+
+fn main() {
+    let source = SNIPPET.repeat(TARGET_SIZE / SNIPPET.len() + 1);
+    // minified scripts put everything on a single line, which must not slow down columns
+    let one_line = source
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("//"))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    bench("multi-line script", &source, true);
+    // the baseline counts columns from the start of the line for every token, it would take
+    // minutes here
+    bench("single-line script", &one_line, false);
+}
+
+fn bench(title: &str, source: &str, with_baseline: bool) {
+    let megabytes = source.len() as f64 / (1024.0 * 1024.0);
+    println!(
+        "{}: scanning {:.1} MiB, best of {} runs",
+        title, megabytes, RUNS
+    );
+
+    let report = |name: &str, scan: &dyn Fn(&str) -> usize| {
+        let mut tokens = 0;
+        let best = (0..RUNS)
+            .map(|_| {
+                let start = Instant::now();
+                tokens = black_box(scan(black_box(source)));
+                start.elapsed()
+            })
+            .min()
+            .unwrap_or(Duration::ZERO);
+        println!(
+            "{:<24} {:>10.2?} {:>8.1} MiB/s {:>10} tokens",
+            name,
+            best,
+            megabytes / best.as_secs_f64(),
+            tokens
+        );
+    };
+
+    if with_baseline {
+        report("baseline scan_tokens", &|source| {
+            baseline::Scanner::new(source, &mut Diagnostics::new())
+                .scan_tokens()
+                .len()
+        });
+    } else {
+        println!("{:<24} {:>10}", "baseline scan_tokens", "skipped");
+    }
+    report("scan_tokens", &|source| {
+        Scanner::new(source).scan_tokens().len()
+    });
+    report("streaming", &|source| Scanner::new(source).count());
+}
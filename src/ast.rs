@@ -0,0 +1,247 @@
+use std::cell::Cell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::scanner::Token;
+
+/// How many scopes up from its use the variable an expression refers to is defined.
+/// The resolver fills it in, variables it leaves unresolved are globals.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Depth(Cell<Option<usize>>);
+
+impl Depth {
+    pub fn resolve(&self, depth: usize) {
+        self.0.set(Some(depth));
+    }
+
+    pub fn get(&self) -> Option<usize> {
+        self.0.get()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Assign {
+        depth: Depth,
+        name: Token<'static>,
+        value: Box<Expr>,
+    },
+    Binary {
+        left: Box<Expr>,
+        operator: Token<'static>,
+        right: Box<Expr>,
+    },
+    Call {
+        callee: Box<Expr>,
+        paren: Token<'static>,
+        arguments: Vec<Expr>,
+    },
+    Get {
+        object: Box<Expr>,
+        name: Token<'static>,
+    },
+    Grouping {
+        expression: Box<Expr>,
+    },
+    Literal {
+        value: Literal,
+    },
+    Logical {
+        left: Box<Expr>,
+        operator: Token<'static>,
+        right: Box<Expr>,
+    },
+    Set {
+        object: Box<Expr>,
+        name: Token<'static>,
+        value: Box<Expr>,
+    },
+    /// Converts any value to a string, string interpolation is lowered to these
+    Stringify {
+        expression: Box<Expr>,
+    },
+    Super {
+        depth: Depth,
+        keyword: Token<'static>,
+        method: Token<'static>,
+    },
+    This {
+        depth: Depth,
+        keyword: Token<'static>,
+    },
+    Unary {
+        operator: Token<'static>,
+        right: Box<Expr>,
+    },
+    Variable {
+        depth: Depth,
+        name: Token<'static>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Block {
+        statements: Vec<Stmt>,
+    },
+    Class {
+        name: Token<'static>,
+        /// Always an `Expr::Variable`, if present
+        superclass: Option<Expr>,
+        methods: Vec<Rc<FunctionDecl>>,
+    },
+    Expression {
+        expression: Expr,
+    },
+    Function(Rc<FunctionDecl>),
+    If {
+        condition: Expr,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+    },
+    Print {
+        expression: Expr,
+    },
+    Return {
+        keyword: Token<'static>,
+        value: Option<Expr>,
+    },
+    Var {
+        name: Token<'static>,
+        initializer: Option<Expr>,
+    },
+    While {
+        condition: Expr,
+        body: Box<Stmt>,
+    },
+}
+
+/// Shared between the AST and every function value created from it at runtime.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDecl {
+    pub name: Token<'static>,
+    pub params: Vec<Token<'static>>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Nil => write!(f, "nil"),
+            Literal::Bool(b) => write!(f, "{}", b),
+            Literal::Number(n) => write!(f, "{}", n),
+            Literal::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// Prints the expression in a lisp like notation, e.g. `(* (- 123) (group 45.67))`
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Assign { name, value, .. } => write!(f, "(= {} {})", name.lexeme, value),
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            }
+            | Expr::Logical {
+                left,
+                operator,
+                right,
+            } => write!(f, "({} {} {})", operator.lexeme, left, right),
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                write!(f, "(call {}", callee)?;
+                for argument in arguments {
+                    write!(f, " {}", argument)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Get { object, name } => write!(f, "(. {} {})", object, name.lexeme),
+            Expr::Grouping { expression } => write!(f, "(group {})", expression),
+            Expr::Literal { value } => write!(f, "{}", value),
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => write!(f, "(= (. {} {}) {})", object, name.lexeme, value),
+            Expr::Stringify { expression } => write!(f, "(str {})", expression),
+            Expr::Super { method, .. } => write!(f, "(super {})", method.lexeme),
+            Expr::This { .. } => write!(f, "this"),
+            Expr::Unary { operator, right } => write!(f, "({} {})", operator.lexeme, right),
+            Expr::Variable { name, .. } => write!(f, "{}", name.lexeme),
+        }
+    }
+}
+
+/// Prints statements in the same lisp like notation, e.g. `(var a (+ 1 2))`
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stmt::Block { statements } => {
+                write!(f, "(block")?;
+                for statement in statements {
+                    write!(f, " {}", statement)?;
+                }
+                write!(f, ")")
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                write!(f, "(class {}", name.lexeme)?;
+                if let Some(superclass) = superclass {
+                    write!(f, " < {}", superclass)?;
+                }
+                for method in methods {
+                    write!(f, " {}", method)?;
+                }
+                write!(f, ")")
+            }
+            Stmt::Expression { expression } => write!(f, "(; {})", expression),
+            Stmt::Function(declaration) => write!(f, "{}", declaration),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => match else_branch {
+                Some(else_branch) => {
+                    write!(f, "(if {} {} {})", condition, then_branch, else_branch)
+                }
+                None => write!(f, "(if {} {})", condition, then_branch),
+            },
+            Stmt::Print { expression } => write!(f, "(print {})", expression),
+            Stmt::Return { value, .. } => match value {
+                Some(value) => write!(f, "(return {})", value),
+                None => write!(f, "(return)"),
+            },
+            Stmt::Var { name, initializer } => match initializer {
+                Some(initializer) => write!(f, "(var {} {})", name.lexeme, initializer),
+                None => write!(f, "(var {})", name.lexeme),
+            },
+            Stmt::While { condition, body } => write!(f, "(while {} {})", condition, body),
+        }
+    }
+}
+
+impl fmt::Display for FunctionDecl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params: Vec<&str> = self.params.iter().map(|p| p.lexeme.as_ref()).collect();
+        write!(f, "(fun {} ({})", self.name.lexeme, params.join(" "))?;
+        for statement in &self.body {
+            write!(f, " {}", statement)?;
+        }
+        write!(f, ")")
+    }
+}
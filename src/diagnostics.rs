@@ -0,0 +1,329 @@
+use std::fmt;
+
+use crate::scanner::{Token, TokenType};
+use crate::span::{LineIndex, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "Error"),
+            Severity::Warning => write!(f, "Warning"),
+        }
+    }
+}
+
+/// Secondary information attached to a diagnostic, e.g. where a variable was declared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub span: Span,
+    pub line: usize,
+    pub column: usize,
+    /// What the diagnostic points at, e.g. `at 'foo'` or `at end`, empty if unknown
+    pub location: String,
+    pub message: String,
+    pub labels: Vec<Label>,
+}
+
+impl Diagnostic {
+    pub fn error(span: Span, line: usize, column: usize, message: &str) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            span,
+            line,
+            column,
+            location: String::new(),
+            message: String::from(message),
+            labels: vec![],
+        }
+    }
+
+    pub fn error_at(token: &Token, message: &str) -> Self {
+        let location = if token.token_type == TokenType::Eof {
+            String::from("at end")
+        } else {
+            format!("at '{}'", token.lexeme)
+        };
+        Diagnostic {
+            severity: Severity::Error,
+            span: token.span,
+            line: token.line,
+            column: token.column,
+            location,
+            message: String::from(message),
+            labels: vec![],
+        }
+    }
+
+    pub fn with_label(&mut self, span: Span, message: &str) -> &mut Self {
+        self.labels.push(Label {
+            span,
+            message: String::from(message),
+        });
+        self
+    }
+
+    /// Renders the diagnostic with the offending source lines, the way rustc does:
+    ///
+    /// ```text
+    /// error: Already a variable with this name in this scope.
+    ///  --> script.lox:3:9
+    ///   |
+    /// 2 |     var a = 1;
+    ///   |         - previously declared here
+    /// 3 |     var a = 2;
+    ///   |         ^
+    /// ```
+    ///
+    /// `source` has to be the one the spans point into, see `Lox::source`. Diagnostics that
+    /// don't fit it fall back to the plain `[line 3:9] Error: ...` format.
+    pub fn render(&self, file_name: &str, source: &str) -> String {
+        let index = LineIndex::new(source);
+        let spans = std::iter::once(self.span).chain(self.labels.iter().map(|label| label.span));
+        if !spans.into_iter().all(|span| index.contains(span)) {
+            return format!("{}\n", self);
+        }
+
+        let mut annotations: Vec<Annotation> = std::iter::once((self.span, '^', ""))
+            .chain(
+                self.labels
+                    .iter()
+                    .map(|label| (label.span, '-', label.message.as_str())),
+            )
+            .map(|(span, marker, message)| Annotation::new(&index, span, marker, message))
+            .collect();
+        annotations.sort_by_key(|annotation| (annotation.line, annotation.column));
+
+        let (line, column) = index.line_column(self.span.start);
+        let gutter = annotations
+            .iter()
+            .map(|annotation| annotation.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let empty = " ".repeat(gutter);
+
+        let severity = self.severity.to_string().to_lowercase();
+        let mut output = format!("{}: {}\n", severity, self.message);
+        output += &format!("{}--> {}:{}:{}\n", empty, file_name, line, column);
+        output += &format!("{} |\n", empty);
+
+        let mut previous_line = None;
+        for annotation in &annotations {
+            let text = index.line_text(annotation.line);
+            if previous_line != Some(annotation.line) {
+                if previous_line.is_some_and(|previous| annotation.line > previous + 1) {
+                    output += "...\n";
+                }
+                output += &format!("{:>gutter$} | {}\n", annotation.line, text);
+                previous_line = Some(annotation.line);
+            }
+
+            // keep tabs, so the markers line up with the text above
+            let padding: String = text
+                .chars()
+                .take(annotation.column - 1)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let markers = annotation.marker.to_string().repeat(annotation.width);
+            let underline = format!("{}{} {}", padding, markers, annotation.message);
+            output += &format!("{} | {}\n", empty, underline.trim_end());
+        }
+
+        output
+    }
+}
+
+/// Prints as `[line 1:5] Error at 'foo': message`
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[line {}:{}] {}", self.line, self.column, self.severity)?;
+        if !self.location.is_empty() {
+            write!(f, " {}", self.location)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// A span resolved to the single source line it starts on, ready to be underlined.
+struct Annotation<'a> {
+    line: usize,
+    column: usize,
+    width: usize,
+    marker: char,
+    message: &'a str,
+}
+
+impl<'a> Annotation<'a> {
+    fn new(index: &LineIndex, span: Span, marker: char, message: &'a str) -> Self {
+        let (line, column) = index.line_column(span.start);
+        let (end_line, end_column) = index.line_column(span.end);
+        // spans reaching past the end of their first line are cut off there
+        let width = if end_line == line {
+            end_column - column
+        } else {
+            index.line_text(line).chars().count() + 1 - column
+        };
+        Annotation {
+            line,
+            column,
+            width: width.max(1),
+            marker,
+            message,
+        }
+    }
+}
+
+/// Collects the problems found while scanning, parsing and resolving a single script.
+/// Every phase reports into the sink it is handed, none of them print anything. Scanner
+/// errors arrive as error tokens and are reported by the parser.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Diagnostics::default()
+    }
+
+    pub fn error(
+        &mut self,
+        span: Span,
+        line: usize,
+        column: usize,
+        message: &str,
+    ) -> &mut Diagnostic {
+        self.push(Diagnostic::error(span, line, column, message))
+    }
+
+    /// Reports an error at `token`, secondary labels can be added to the returned diagnostic.
+    pub fn error_at(&mut self, token: &Token, message: &str) -> &mut Diagnostic {
+        self.push(Diagnostic::error_at(token, message))
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) -> &mut Diagnostic {
+        self.diagnostics.push(diagnostic);
+        self.diagnostics
+            .last_mut()
+            .expect("Just pushed a diagnostic")
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter()
+    }
+
+    pub fn into_vec(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn token(source: &str, index: usize) -> Token<'static> {
+        Scanner::new(source).scan_tokens().remove(index)
+    }
+
+    #[test]
+    fn it_prints_a_single_line_without_stray_spaces() {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.error(Span::new(0, 1), 1, 1, "Unexpected character: @");
+        diagnostics.error_at(&token("a;", 1), "Expect expression.");
+        let lines: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "[line 1:1] Error: Unexpected character: @",
+                "[line 1:2] Error at ';': Expect expression."
+            ]
+        );
+    }
+
+    #[test]
+    fn it_renders_the_source_line_with_an_underline() {
+        let source = "var a = 1;\nprint a +;\n";
+        let diagnostic = Diagnostic::error_at(&token(source, 8), "Expect expression.");
+        assert_eq!(
+            diagnostic.render("script.lox", source),
+            "\
+error: Expect expression.
+ --> script.lox:2:10
+  |
+2 | print a +;
+  |          ^
+"
+        );
+    }
+
+    #[test]
+    fn it_underlines_the_whole_token() {
+        let source = "print unknown;";
+        let diagnostic = Diagnostic::error_at(&token(source, 1), "Undefined variable.");
+        assert!(diagnostic
+            .render("script.lox", source)
+            .ends_with("1 | print unknown;\n  |       ^^^^^^^\n"));
+    }
+
+    #[test]
+    fn it_renders_secondary_labels_in_source_order() {
+        let source = "{\n  var a = 1;\n\n\n\n\n\n\n\n  var a = 2;\n}";
+        let first = token(source, 2);
+        let second = token(source, 7);
+        let mut diagnostic =
+            Diagnostic::error_at(&second, "Already a variable with this name in this scope.");
+        diagnostic.with_label(first.span, "previously declared here");
+        assert_eq!(
+            diagnostic.render("script.lox", source),
+            "\
+error: Already a variable with this name in this scope.
+  --> script.lox:10:7
+   |
+ 2 |   var a = 1;
+   |       - previously declared here
+...
+10 |   var a = 2;
+   |       ^
+"
+        );
+    }
+
+    #[test]
+    fn it_falls_back_to_a_single_line_for_spans_outside_the_source() {
+        let source = "fun f() { return -nil; }";
+        let diagnostic = Diagnostic::error_at(&token(source, 6), "Operand must be a number.");
+        assert_eq!(
+            diagnostic.render("<stdin>", "f();"),
+            "[line 1:18] Error at '-': Operand must be a number.\n"
+        );
+    }
+
+    #[test]
+    fn it_points_past_the_last_character_at_the_end_of_the_input() {
+        let source = "print 1";
+        let tokens = Scanner::new(source).scan_tokens();
+        let diagnostic = Diagnostic::error_at(tokens.last().unwrap(), "Expect ';' after value.");
+        assert!(diagnostic
+            .render("script.lox", source)
+            .ends_with("1 | print 1\n  |        ^\n"));
+    }
+}
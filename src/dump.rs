@@ -0,0 +1,173 @@
+use std::fmt::Write;
+
+use crate::scanner::{LiteralType, Scanner, Token};
+
+/// How `rlox tokens` prints the tokens of a script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One JSON object per line, for tools
+    Json,
+    /// Aligned columns, for people
+    Table,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "json" => Ok(Format::Json),
+            "table" => Ok(Format::Table),
+            _ => Err(format!(
+                "Unknown format '{}', expect 'json' or 'table'.",
+                name
+            )),
+        }
+    }
+}
+
+/// Scans `source` and prints every token, including error tokens and the final `Eof`, one per
+/// line. Scanner errors are part of the output instead of stopping it.
+pub fn dump_tokens(source: &str, format: Format) -> String {
+    let mut output = String::new();
+    if format == Format::Table {
+        output += &table_row("LINE:COL", "SPAN", "TYPE", "LEXEME", "LITERAL");
+    }
+    for token in Scanner::new(source) {
+        output += &match format {
+            Format::Json => json_line(&token),
+            Format::Table => table_line(&token),
+        };
+    }
+    output
+}
+
+/// `{"type":"Number","lexeme":"1.5","literal":1.5,"line":1,"column":7,"span":{"start":6,"end":9}}`,
+/// error tokens carry their message in an extra `error` field.
+fn json_line(token: &Token) -> String {
+    let literal = match &token.literal {
+        LiteralType::StringLiteral(value) => json_string(value),
+        // the scanner rejects literals too large for a finite number, which JSON can't represent
+        LiteralType::NumberLiteral(number) => number.to_string(),
+        LiteralType::Nil | LiteralType::Error(_) => String::from("null"),
+    };
+    let mut line = format!(
+        "{{\"type\":\"{:?}\",\"lexeme\":{},\"literal\":{},\"line\":{},\"column\":{},\"span\":{{\"start\":{},\"end\":{}}}",
+        token.token_type,
+        json_string(&token.lexeme),
+        literal,
+        token.line,
+        token.column,
+        token.span.start,
+        token.span.end
+    );
+    if let LiteralType::Error(diagnostic) = &token.literal {
+        line += &format!(",\"error\":{}", json_string(&diagnostic.message));
+    }
+    line + "}\n"
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            '\n' => quoted += "\\n",
+            '\r' => quoted += "\\r",
+            '\t' => quoted += "\\t",
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted + "\""
+}
+
+fn table_line(token: &Token) -> String {
+    let literal = match &token.literal {
+        LiteralType::Nil => String::new(),
+        LiteralType::StringLiteral(value) => format!("{:?}", value),
+        LiteralType::NumberLiteral(number) => number.to_string(),
+        LiteralType::Error(diagnostic) => diagnostic.message.clone(),
+    };
+    table_row(
+        &format!("{}:{}", token.line, token.column),
+        &format!("{}..{}", token.span.start, token.span.end),
+        &format!("{:?}", token.token_type),
+        // keeps lexemes spanning several lines on a single row
+        &token.lexeme.escape_debug().to_string(),
+        &literal,
+    )
+}
+
+fn table_row(position: &str, span: &str, token_type: &str, lexeme: &str, literal: &str) -> String {
+    let row = format!(
+        "{:<9} {:<11} {:<13} {:<15} {}",
+        position, span, token_type, lexeme, literal
+    );
+    row.trim_end().to_string() + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_prints_one_json_object_per_token() {
+        assert_eq!(
+            dump_tokens("var a = 1.5;", Format::Json),
+            "\
+{\"type\":\"Var\",\"lexeme\":\"var\",\"literal\":null,\"line\":1,\"column\":1,\"span\":{\"start\":0,\"end\":3}}
+{\"type\":\"Identifier\",\"lexeme\":\"a\",\"literal\":null,\"line\":1,\"column\":5,\"span\":{\"start\":4,\"end\":5}}
+{\"type\":\"Equal\",\"lexeme\":\"=\",\"literal\":null,\"line\":1,\"column\":7,\"span\":{\"start\":6,\"end\":7}}
+{\"type\":\"Number\",\"lexeme\":\"1.5\",\"literal\":1.5,\"line\":1,\"column\":9,\"span\":{\"start\":8,\"end\":11}}
+{\"type\":\"Semicolon\",\"lexeme\":\";\",\"literal\":null,\"line\":1,\"column\":12,\"span\":{\"start\":11,\"end\":12}}
+{\"type\":\"Eof\",\"lexeme\":\"\",\"literal\":null,\"line\":1,\"column\":13,\"span\":{\"start\":12,\"end\":12}}
+"
+        );
+    }
+
+    #[test]
+    fn it_escapes_json_strings() {
+        let output = dump_tokens("\"a\\\"\\\\\tb\n\u{1}ä\"", Format::Json);
+        let first = output.lines().next().unwrap();
+        assert!(first.starts_with(
+            "{\"type\":\"TString\",\"lexeme\":\"\\\"a\\\\\\\"\\\\\\\\\\tb\\n\\u0001ä\\\"\",\"literal\":\"a\\\"\\\\\\tb\\n\\u0001ä\","
+        ));
+    }
+
+    #[test]
+    fn it_keeps_errors_in_json() {
+        let output = dump_tokens("@ 1e999", Format::Json);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[0],
+            "{\"type\":\"Error\",\"lexeme\":\"@\",\"literal\":null,\"line\":1,\"column\":1,\"span\":{\"start\":0,\"end\":1},\"error\":\"Unexpected character: @\"}"
+        );
+        assert!(lines[1].ends_with("\"error\":\"Number literal is too large.\"}"));
+    }
+
+    #[test]
+    fn it_prints_a_table() {
+        assert_eq!(
+            dump_tokens("print \"a\nb\";\n@", Format::Table),
+            "\
+LINE:COL  SPAN        TYPE          LEXEME          LITERAL
+1:1       0..5        Print         print
+1:7       6..11       TString       \\\"a\\nb\\\"        \"a\\nb\"
+2:3       11..12      Semicolon     ;
+3:1       13..14      Error         @               Unexpected character: @
+3:2       14..14      Eof
+"
+        );
+    }
+
+    #[test]
+    fn it_parses_format_names() {
+        assert_eq!("json".parse(), Ok(Format::Json));
+        assert_eq!("table".parse(), Ok(Format::Table));
+        assert!("yaml".parse::<Format>().is_err());
+    }
+}
@@ -0,0 +1,871 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::ast::{Depth, Expr, Stmt};
+use crate::class::{LoxClass, LoxInstance};
+use crate::diagnostics::Diagnostic;
+use crate::environment::Environment;
+use crate::function::{LoxFunction, NativeFunction};
+use crate::scanner::{Token, TokenType::*};
+use crate::value::Value;
+
+#[derive(Debug)]
+pub struct RuntimeError {
+    // boxed to keep the results carrying runtime errors small
+    pub token: Box<Token<'static>>,
+    pub message: String,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n[line {}]", self.message, self.token.line)
+    }
+}
+
+impl RuntimeError {
+    /// For rendering runtime errors with the same source snippets as static ones
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::error_at(&self.token, &self.message)
+    }
+
+    pub(crate) fn new(token: &Token, message: &str) -> Self {
+        RuntimeError {
+            token: Box::new(token.clone().into_owned()),
+            message: String::from(message),
+        }
+    }
+}
+
+pub(crate) type RuntimeResult<T> = Result<T, RuntimeError>;
+
+/// Why the execution of statements stopped early: either an error or a `return`
+/// unwinding up to the enclosing function call.
+#[derive(Debug)]
+pub(crate) enum Unwind {
+    Error(RuntimeError),
+    Return(Value),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+const NATIVES: [NativeFunction; 1] = [NativeFunction {
+    name: "clock",
+    arity: 0,
+    function: clock,
+}];
+
+fn clock(_arguments: &[Value]) -> Value {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time should be after the unix epoch");
+    Value::Number(now.as_secs_f64())
+}
+
+/// Lox calls nest Rust calls, deeper recursion would overflow the native stack. Leaves room
+/// for debug builds on threads with small stacks, like test threads, clox allows 64 frames.
+const MAX_CALL_DEPTH: usize = 128;
+
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    call_depth: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        let globals = Rc::new(RefCell::new(Environment::default()));
+        for native in NATIVES {
+            let name = native.name;
+            globals
+                .borrow_mut()
+                .define(name, Value::NativeFunction(Rc::new(native)));
+        }
+
+        Interpreter {
+            environment: Rc::clone(&globals),
+            globals,
+            call_depth: 0,
+        }
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter::default()
+    }
+
+    /// Runs `statements`, returning the value of the last one if it is an expression
+    /// statement and `nil` otherwise. Statements should have been checked by the `Resolver`,
+    /// local variables are looked up as globals otherwise.
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<Value, RuntimeError> {
+        let Some((last, statements)) = statements.split_last() else {
+            return Ok(Value::Nil);
+        };
+
+        for statement in statements {
+            self.execute_top_level(statement)?;
+        }
+
+        match last {
+            Stmt::Expression { expression } => self.evaluate(expression),
+            statement => {
+                self.execute_top_level(statement)?;
+                Ok(Value::Nil)
+            }
+        }
+    }
+
+    fn execute_top_level(&mut self, statement: &Stmt) -> Result<(), RuntimeError> {
+        match self.execute(statement) {
+            Ok(()) => Ok(()),
+            Err(Unwind::Error(error)) => Err(error),
+            Err(Unwind::Return(_)) => unreachable!("Returning outside of a function is an error"),
+        }
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+        match stmt {
+            Stmt::Block { statements } => {
+                let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+                self.execute_block(statements, Rc::new(RefCell::new(environment)))?;
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let superclass = match superclass {
+                    Some(superclass) => match self.evaluate(superclass)? {
+                        Value::Class(class) => Some(class),
+                        _ => {
+                            let Expr::Variable { name, .. } = superclass else {
+                                unreachable!("The parser only creates variables as superclass")
+                            };
+                            let error = RuntimeError::new(name, "Superclass must be a class.");
+                            return Err(error.into());
+                        }
+                    },
+                    None => None,
+                };
+
+                // methods close over an extra environment binding `super`
+                let enclosing = Rc::clone(&self.environment);
+                if let Some(superclass) = &superclass {
+                    let mut environment = Environment::with_enclosing(Rc::clone(&enclosing));
+                    environment.define("super", Value::Class(Rc::clone(superclass)));
+                    self.environment = Rc::new(RefCell::new(environment));
+                }
+
+                let methods = methods
+                    .iter()
+                    .map(|method| {
+                        let function = LoxFunction::new(
+                            Rc::clone(method),
+                            Rc::clone(&self.environment),
+                            method.name.lexeme == "init",
+                        );
+                        (method.name.lexeme.to_string(), Rc::new(function))
+                    })
+                    .collect();
+                let class = LoxClass::new(&name.lexeme, superclass, methods);
+
+                self.environment = enclosing;
+                self.environment
+                    .borrow_mut()
+                    .define(&name.lexeme, Value::Class(Rc::new(class)));
+            }
+            Stmt::Expression { expression } => {
+                self.evaluate(expression)?;
+            }
+            Stmt::Function(declaration) => {
+                let function =
+                    LoxFunction::new(Rc::clone(declaration), Rc::clone(&self.environment), false);
+                self.environment
+                    .borrow_mut()
+                    .define(&declaration.name.lexeme, Value::Function(Rc::new(function)));
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if self.evaluate(condition)?.is_truthy() {
+                    self.execute(then_branch)?;
+                } else if let Some(else_branch) = else_branch {
+                    self.execute(else_branch)?;
+                }
+            }
+            Stmt::Print { expression } => {
+                let value = self.evaluate(expression)?;
+                println!("{}", value);
+            }
+            Stmt::Return { keyword, value } => {
+                // the resolver rejects these, statements it hasn't checked may still contain them
+                if self.call_depth == 0 {
+                    let message = "Can't return from top-level code.";
+                    return Err(RuntimeError::new(keyword, message).into());
+                }
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::Nil,
+                };
+                return Err(Unwind::Return(value));
+            }
+            Stmt::Var { name, initializer } => {
+                let value = match initializer {
+                    Some(initializer) => self.evaluate(initializer)?,
+                    None => Value::Nil,
+                };
+                self.environment.borrow_mut().define(&name.lexeme, value);
+            }
+            Stmt::While { condition, body } => {
+                while self.evaluate(condition)?.is_truthy() {
+                    self.execute(body)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs `statements` in `environment`, restoring the current environment afterwards,
+    /// regardless of whether one of the statements failed.
+    pub(crate) fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<(), Unwind> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.execute(statement));
+        self.environment = previous;
+        result
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> RuntimeResult<Value> {
+        // keeps this frame small, as it is repeated for every nested expression, the arms
+        // needing more than a few locals live in their own functions
+        match expr {
+            Expr::Assign { depth, name, value } => self.assign(depth, name, value),
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                self.binary(operator, left, right)
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => self.call_expression(callee, paren, arguments),
+            Expr::Get { object, name } => self.get(object, name),
+            Expr::Grouping { expression } => self.evaluate(expression),
+            Expr::Literal { value } => Ok(Value::from(value)),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => self.logical(left, operator, right),
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => self.set(object, name, value),
+            Expr::Stringify { expression } => {
+                Ok(Value::String(self.evaluate(expression)?.to_string()))
+            }
+            Expr::Super {
+                depth,
+                keyword,
+                method,
+            } => self.super_method(depth, keyword, method),
+            Expr::This { depth, keyword } => self.look_up_variable(depth, keyword),
+            Expr::Unary { operator, right } => {
+                let right = self.evaluate(right)?;
+                self.unary(operator, right)
+            }
+            Expr::Variable { depth, name } => self.look_up_variable(depth, name),
+        }
+    }
+
+    fn assign(&mut self, depth: &Depth, name: &Token, value: &Expr) -> RuntimeResult<Value> {
+        let value = self.evaluate(value)?;
+        match depth.get() {
+            Some(distance) => {
+                Environment::assign_at(&self.environment, distance, &name.lexeme, value.clone())
+            }
+            None => self.globals.borrow_mut().assign(name, value.clone())?,
+        }
+        Ok(value)
+    }
+
+    fn call_expression(
+        &mut self,
+        callee: &Expr,
+        paren: &Token,
+        arguments: &[Expr],
+    ) -> RuntimeResult<Value> {
+        let callee = self.evaluate(callee)?;
+        let arguments = arguments
+            .iter()
+            .map(|argument| self.evaluate(argument))
+            .collect::<RuntimeResult<Vec<Value>>>()?;
+        self.call(callee, paren, arguments)
+    }
+
+    fn get(&mut self, object: &Expr, name: &Token) -> RuntimeResult<Value> {
+        match self.evaluate(object)? {
+            Value::Instance(instance) => LoxInstance::get(&instance, name),
+            _ => Err(RuntimeError::new(name, "Only instances have properties.")),
+        }
+    }
+
+    fn logical(&mut self, left: &Expr, operator: &Token, right: &Expr) -> RuntimeResult<Value> {
+        let left = self.evaluate(left)?;
+        // short circuit, returning the operand that decided the outcome
+        if operator.token_type == Or {
+            if left.is_truthy() {
+                return Ok(left);
+            }
+        } else if !left.is_truthy() {
+            return Ok(left);
+        }
+        self.evaluate(right)
+    }
+
+    fn set(&mut self, object: &Expr, name: &Token, value: &Expr) -> RuntimeResult<Value> {
+        let Value::Instance(instance) = self.evaluate(object)? else {
+            return Err(RuntimeError::new(name, "Only instances have fields."));
+        };
+        let value = self.evaluate(value)?;
+        instance.borrow_mut().set(name, value.clone());
+        Ok(value)
+    }
+
+    fn super_method(&self, depth: &Depth, keyword: &Token, method: &Token) -> RuntimeResult<Value> {
+        // only unresolved if the resolver hasn't checked the statements
+        let Some(distance) = depth.get() else {
+            let message = "Can't use 'super' outside of a class.";
+            return Err(RuntimeError::new(keyword, message));
+        };
+        let Value::Class(superclass) =
+            Environment::get_at(&self.environment, distance, &keyword.lexeme)
+        else {
+            unreachable!("'super' is always bound to a class");
+        };
+        // `this` is always bound in the environment right inside the one holding `super`
+        let Value::Instance(object) = Environment::get_at(&self.environment, distance - 1, "this")
+        else {
+            unreachable!("'this' is always bound to an instance");
+        };
+
+        match superclass.find_method(method.lexeme.as_ref()) {
+            Some(method) => Ok(Value::Function(Rc::new(method.bind(object)))),
+            None => {
+                let message = format!("Undefined property '{}'.", method.lexeme);
+                Err(RuntimeError::new(method, &message))
+            }
+        }
+    }
+
+    fn unary(&self, operator: &Token, right: Value) -> RuntimeResult<Value> {
+        match operator.token_type {
+            Bang => Ok(Value::Bool(!right.is_truthy())),
+            Minus => match right {
+                Value::Number(n) => Ok(Value::Number(-n)),
+                _ => Err(RuntimeError::new(operator, "Operand must be a number.")),
+            },
+            _ => unreachable!("Invalid unary operator {:?}", operator.token_type),
+        }
+    }
+
+    fn look_up_variable(&self, depth: &Depth, name: &Token) -> RuntimeResult<Value> {
+        match depth.get() {
+            Some(distance) => Ok(Environment::get_at(
+                &self.environment,
+                distance,
+                &name.lexeme,
+            )),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    fn call(
+        &mut self,
+        callee: Value,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> RuntimeResult<Value> {
+        let arity = match &callee {
+            Value::Function(function) => function.arity(),
+            Value::NativeFunction(function) => function.arity,
+            Value::Class(class) => class.arity(),
+            _ => {
+                return Err(RuntimeError::new(
+                    paren,
+                    "Can only call functions and classes.",
+                ))
+            }
+        };
+
+        if arguments.len() != arity {
+            let message = format!("Expected {} arguments but got {}.", arity, arguments.len());
+            return Err(RuntimeError::new(paren, &message));
+        }
+        if self.call_depth >= MAX_CALL_DEPTH {
+            return Err(RuntimeError::new(paren, "Stack overflow."));
+        }
+
+        self.call_depth += 1;
+        let result = self.call_checked(callee, arguments);
+        self.call_depth -= 1;
+        result
+    }
+
+    /// Calls `callee`, which is known to be callable with `arguments`.
+    fn call_checked(&mut self, callee: Value, arguments: Vec<Value>) -> RuntimeResult<Value> {
+        match callee {
+            Value::Function(function) => function.call(self, arguments),
+            Value::NativeFunction(function) => function.call(arguments),
+            Value::Class(class) => {
+                let instance = Rc::new(RefCell::new(LoxInstance::new(Rc::clone(&class))));
+                if let Some(initializer) = class.find_method("init") {
+                    initializer
+                        .bind(Rc::clone(&instance))
+                        .call(self, arguments)?;
+                }
+                Ok(Value::Instance(instance))
+            }
+            _ => unreachable!("Only callables have an arity"),
+        }
+    }
+
+    fn binary(&self, operator: &Token, left: Value, right: Value) -> RuntimeResult<Value> {
+        match operator.token_type {
+            EqualEqual => return Ok(Value::Bool(left == right)),
+            BangEqual => return Ok(Value::Bool(left != right)),
+            Plus => {
+                return match (left, right) {
+                    (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l + r)),
+                    (Value::String(l), Value::String(r)) => Ok(Value::String(l + &r)),
+                    _ => Err(RuntimeError::new(
+                        operator,
+                        "Operands must be two numbers or two strings.",
+                    )),
+                }
+            }
+            _ => {}
+        }
+
+        let (l, r) = match (left, right) {
+            (Value::Number(l), Value::Number(r)) => (l, r),
+            _ => return Err(RuntimeError::new(operator, "Operands must be numbers.")),
+        };
+
+        match operator.token_type {
+            Minus => Ok(Value::Number(l - r)),
+            Slash => Ok(Value::Number(l / r)),
+            Star => Ok(Value::Number(l * r)),
+            Greater => Ok(Value::Bool(l > r)),
+            GreaterEqual => Ok(Value::Bool(l >= r)),
+            Less => Ok(Value::Bool(l < r)),
+            LessEqual => Ok(Value::Bool(l <= r)),
+            _ => unreachable!("Invalid binary operator {:?}", operator.token_type),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::assert_eq;
+
+    use super::*;
+    use crate::diagnostics::Diagnostics;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+
+    fn parse(input: &str) -> Vec<Stmt> {
+        let mut diagnostics = Diagnostics::new();
+        let tokens = Scanner::new(input).scan_tokens();
+        let statements = Parser::new(tokens, &mut diagnostics).parse();
+        assert!(!diagnostics.has_errors(), "Should parse");
+        statements
+    }
+
+    fn run(input: &str) -> RuntimeResult<Interpreter> {
+        let statements = parse(input);
+        let mut interpreter = Interpreter::new();
+        let mut diagnostics = Diagnostics::new();
+        Resolver::new(&mut diagnostics).resolve(&statements);
+        assert!(!diagnostics.has_errors(), "Should resolve");
+        for statement in &statements {
+            if let Err(Unwind::Error(error)) = interpreter.execute(statement) {
+                return Err(error);
+            }
+        }
+        Ok(interpreter)
+    }
+
+    fn global(interpreter: &Interpreter, name: &str) -> Value {
+        let token = Scanner::new(name).scan_tokens().remove(0);
+        interpreter.globals.borrow().get(&token).unwrap()
+    }
+
+    fn eval(input: &str) -> RuntimeResult<Value> {
+        let interpreter = run(&format!("var result = {};", input))?;
+        Ok(global(&interpreter, "result"))
+    }
+
+    #[test]
+    fn it_does_arithmetic() {
+        assert_eq!(eval("1 + 2 * 3").unwrap(), Value::Number(7.0));
+        assert_eq!(eval("(1 + 2) * 3").unwrap(), Value::Number(9.0));
+        assert_eq!(eval("-4 / 2").unwrap(), Value::Number(-2.0));
+    }
+
+    #[test]
+    fn it_computes_in_double_precision() {
+        assert_eq!(eval("16777216 + 1").unwrap(), Value::Number(16777217.0));
+        assert_eq!(eval("0.1 + 0.2").unwrap(), Value::Number(0.1 + 0.2));
+        assert_eq!(eval("0xff + 0b1 + 1e3").unwrap(), Value::Number(1256.0));
+    }
+
+    #[test]
+    fn it_concatenates_strings() {
+        assert_eq!(
+            eval("\"foo\" + \"bar\"").unwrap(),
+            Value::String(String::from("foobar"))
+        );
+    }
+
+    #[test]
+    fn it_interpolates_values_into_strings() {
+        let interpreter = run("var name = \"lox\"; var count = 3; class A {} \
+             var result = \"${name} x${count + 1}: ${nil} ${A} ${\"in${true}er\"}\";")
+        .unwrap();
+        assert_eq!(
+            global(&interpreter, "result"),
+            Value::String(String::from("lox x4: nil A intrueer"))
+        );
+    }
+
+    #[test]
+    fn it_treats_nil_and_false_as_falsey() {
+        assert_eq!(eval("!nil").unwrap(), Value::Bool(true));
+        assert_eq!(eval("!false").unwrap(), Value::Bool(true));
+        assert_eq!(eval("!0").unwrap(), Value::Bool(false));
+        assert_eq!(eval("!\"\"").unwrap(), Value::Bool(false));
+    }
+
+    #[test]
+    fn it_compares_for_equality() {
+        assert_eq!(eval("1 == 1").unwrap(), Value::Bool(true));
+        assert_eq!(eval("nil == nil").unwrap(), Value::Bool(true));
+        assert_eq!(eval("nil == false").unwrap(), Value::Bool(false));
+        assert_eq!(eval("\"1\" == 1").unwrap(), Value::Bool(false));
+        assert_eq!(eval("\"a\" != \"b\"").unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn it_short_circuits_logical_operators() {
+        assert_eq!(
+            eval("nil or \"yes\"").unwrap(),
+            Value::String(String::from("yes"))
+        );
+        assert_eq!(eval("false and undefined").unwrap(), Value::Bool(false));
+    }
+
+    #[test]
+    fn it_reports_type_errors_with_the_operator_line() {
+        let error = eval("1 +\n \"a\"").unwrap_err();
+        assert_eq!(
+            error.message,
+            "Operands must be two numbers or two strings."
+        );
+        assert_eq!(error.token.line, 1);
+
+        let error = eval("-\"a\"").unwrap_err();
+        assert_eq!(error.message, "Operand must be a number.");
+
+        let error = eval("1 <\n\n nil").unwrap_err();
+        assert_eq!(error.message, "Operands must be numbers.");
+    }
+
+    #[test]
+    fn it_runs_loops() {
+        let interpreter = run("var i = 0; while (i < 10) i = i + 1;").unwrap();
+        assert_eq!(global(&interpreter, "i"), Value::Number(10.0));
+    }
+
+    #[test]
+    fn it_shadows_and_restores_variables_in_blocks() {
+        let interpreter = run("var a = 1; var b; { var a = 2; b = a; } var c = a;").unwrap();
+        assert_eq!(global(&interpreter, "b"), Value::Number(2.0));
+        assert_eq!(global(&interpreter, "c"), Value::Number(1.0));
+    }
+
+    #[test]
+    fn it_assigns_to_enclosing_scopes() {
+        let interpreter = run("var a = 1; { { a = a + 1; } }").unwrap();
+        assert_eq!(global(&interpreter, "a"), Value::Number(2.0));
+    }
+
+    #[test]
+    fn it_restores_the_environment_after_a_runtime_error() {
+        let mut interpreter = Interpreter::new();
+        let statements = parse("{ var a = 1; -nil; }");
+        assert!(interpreter.execute(&statements[0]).is_err());
+        assert!(Rc::ptr_eq(&interpreter.environment, &interpreter.globals));
+    }
+
+    #[test]
+    fn it_reports_undefined_variables_by_name_and_line() {
+        let error = run("var a;\n{ print b; }").err().unwrap();
+        assert_eq!(error.message, "Undefined variable 'b'.");
+        assert_eq!(error.token.line, 2);
+
+        let error = run("{\n c = 1; }").err().unwrap();
+        assert_eq!(error.message, "Undefined variable 'c'.");
+        assert_eq!(error.token.line, 2);
+    }
+
+    #[test]
+    fn it_reports_unresolved_statements_instead_of_panicking() {
+        let mut interpreter = Interpreter::new();
+        let error = interpreter
+            .interpret(&parse("print 1;\nreturn 2;"))
+            .unwrap_err();
+        assert_eq!(error.message, "Can't return from top-level code.");
+        assert_eq!(error.token.line, 2);
+
+        let program = "class A { m() { return 1; } } class B < A { m() { return super.m(); } }
+            B().m();";
+        let error = interpreter.interpret(&parse(program)).unwrap_err();
+        assert_eq!(error.message, "Can't use 'super' outside of a class.");
+
+        let program = "fun f() { if (true) return 1; } f();";
+        assert_eq!(
+            interpreter.interpret(&parse(program)).unwrap(),
+            Value::Number(1.0)
+        );
+    }
+
+    #[test]
+    fn it_calls_functions_and_returns_values() {
+        let interpreter = run("fun add(a, b) { return a + b; } var result = add(1, 2);").unwrap();
+        assert_eq!(global(&interpreter, "result"), Value::Number(3.0));
+
+        let interpreter = run("fun f() { } var result = f();").unwrap();
+        assert_eq!(global(&interpreter, "result"), Value::Nil);
+    }
+
+    #[test]
+    fn it_reports_unbounded_recursion_as_a_stack_overflow() {
+        let error = run("fun f(n) { return f(n + 1); }\nf(0);").err().unwrap();
+        assert_eq!(error.message, "Stack overflow.");
+        assert_eq!(error.token.line, 1);
+
+        let program = "fun f(n) { return 1 + f(n + 1); } f(0);";
+        assert_eq!(run(program).err().unwrap().message, "Stack overflow.");
+        let program = "class A { init() { this.m(); } m() { A(); } } A();";
+        assert_eq!(run(program).err().unwrap().message, "Stack overflow.");
+
+        // the depth is back to zero afterwards
+        let program = "fun f(n) { if (n > 0) return f(n - 1); return n; } var result = f(120);";
+        let interpreter = run(program).unwrap();
+        assert_eq!(global(&interpreter, "result"), Value::Number(0.0));
+        assert_eq!(interpreter.call_depth, 0);
+    }
+
+    #[test]
+    fn it_unwinds_return_from_nested_loops() {
+        let program = "
+            fun find() {
+                for (var i = 0; i < 10; i = i + 1) {
+                    while (true) {
+                        if (i == 3) return i;
+                        i = i + 1;
+                    }
+                }
+            }
+            var result = find();
+        ";
+        let interpreter = run(program).unwrap();
+        assert_eq!(global(&interpreter, "result"), Value::Number(3.0));
+
+        let program = "
+            fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+            var result = fib(10);
+        ";
+        let interpreter = run(program).unwrap();
+        assert_eq!(global(&interpreter, "result"), Value::Number(55.0));
+    }
+
+    #[test]
+    fn it_captures_the_defining_environment_in_closures() {
+        let program = "
+            fun make_counter() {
+                var i = 0;
+                fun count() { i = i + 1; return i; }
+                return count;
+            }
+            var counter = make_counter();
+            counter();
+            var result = counter();
+            var other = make_counter()();
+        ";
+        let interpreter = run(program).unwrap();
+        assert_eq!(global(&interpreter, "result"), Value::Number(2.0));
+        assert_eq!(global(&interpreter, "other"), Value::Number(1.0));
+    }
+
+    #[test]
+    fn it_checks_arity() {
+        let error = run("fun f(a) {}\nf(1, 2);").err().unwrap();
+        assert_eq!(error.message, "Expected 1 arguments but got 2.");
+        assert_eq!(error.token.line, 2);
+
+        let error = run("clock(1);").err().unwrap();
+        assert_eq!(error.message, "Expected 0 arguments but got 1.");
+    }
+
+    #[test]
+    fn it_only_calls_callables() {
+        let error = run("\"not a function\"();").err().unwrap();
+        assert_eq!(error.message, "Can only call functions and classes.");
+    }
+
+    #[test]
+    fn it_provides_a_native_clock() {
+        let interpreter = run("var result = clock();").unwrap();
+        assert!(matches!(global(&interpreter, "result"), Value::Number(n) if n > 0.0));
+        assert_eq!(global(&interpreter, "clock").to_string(), "<native fn>");
+    }
+
+    #[test]
+    fn it_binds_closures_statically() {
+        let program = "
+            var a = \"global\";
+            var first;
+            var second;
+            {
+                fun show() { return a; }
+                first = show();
+                var a = \"block\";
+                second = show();
+            }
+        ";
+        let interpreter = run(program).unwrap();
+        assert_eq!(
+            global(&interpreter, "first"),
+            Value::String(String::from("global"))
+        );
+        assert_eq!(
+            global(&interpreter, "second"),
+            Value::String(String::from("global"))
+        );
+    }
+
+    #[test]
+    fn it_creates_instances_with_fields_and_methods() {
+        let program = "
+            class Point {
+                init(x, y) { this.x = x; this.y = y; }
+                sum() { return this.x + this.y; }
+            }
+            var p = Point(1, 2);
+            var sum = p.sum();
+            p.x = 10;
+            var method = p.sum;
+            var bound = method();
+        ";
+        let interpreter = run(program).unwrap();
+        assert_eq!(global(&interpreter, "sum"), Value::Number(3.0));
+        assert_eq!(global(&interpreter, "bound"), Value::Number(12.0));
+        assert_eq!(global(&interpreter, "Point").to_string(), "Point");
+        assert_eq!(global(&interpreter, "p").to_string(), "Point instance");
+    }
+
+    #[test]
+    fn it_returns_this_from_initializers() {
+        let program = "
+            class A {
+                init() { this.count = 0; return; }
+            }
+            var a = A();
+            a.count = 1;
+            var again = a.init();
+        ";
+        let interpreter = run(program).unwrap();
+        assert_eq!(global(&interpreter, "again"), global(&interpreter, "a"));
+    }
+
+    #[test]
+    fn it_reports_property_errors() {
+        let error = run("class A {}\nA().missing;").err().unwrap();
+        assert_eq!(error.message, "Undefined property 'missing'.");
+        assert_eq!(error.token.line, 2);
+
+        let error = run("var a = 1; a.b;").err().unwrap();
+        assert_eq!(error.message, "Only instances have properties.");
+
+        let error = run("var a = 1; a.b = 2;").err().unwrap();
+        assert_eq!(error.message, "Only instances have fields.");
+
+        let error = run("class A { init(a) {} } A();").err().unwrap();
+        assert_eq!(error.message, "Expected 1 arguments but got 0.");
+    }
+
+    #[test]
+    fn it_inherits_methods_and_calls_super() {
+        let program = "
+            class A {
+                init(name) { this.name = name; }
+                greet() { return \"A \" + this.name; }
+                only_a() { return \"only a\"; }
+            }
+            class B < A {
+                init(name) { super.init(name + \"!\"); }
+                greet() { return \"B \" + super.greet(); }
+            }
+            class C < B {}
+            var c = C(\"c\");
+            var greeting = c.greet();
+            var inherited = c.only_a();
+        ";
+        let interpreter = run(program).unwrap();
+        assert_eq!(
+            global(&interpreter, "greeting"),
+            Value::String(String::from("B A c!"))
+        );
+        assert_eq!(
+            global(&interpreter, "inherited"),
+            Value::String(String::from("only a"))
+        );
+    }
+
+    #[test]
+    fn it_reports_superclass_errors() {
+        let error = run("var NotAClass = 1;\nclass A < NotAClass {}")
+            .err()
+            .unwrap();
+        assert_eq!(error.message, "Superclass must be a class.");
+        assert_eq!(error.token.line, 2);
+
+        let error = run("class A {}\nclass B < A { m() { return super.missing(); } }\nB().m();")
+            .err()
+            .unwrap();
+        assert_eq!(error.message, "Undefined property 'missing'.");
+        assert_eq!(error.token.line, 2);
+    }
+}
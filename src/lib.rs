@@ -0,0 +1,191 @@
+//! A tree-walking interpreter for the lox language from `Crafting Interpreters`.
+//!
+//! Embedding it only takes a [`Lox`] session, which keeps its global state between calls:
+//!
+//! ```
+//! use rlox::{Lox, value::Value};
+//!
+//! let mut lox = Lox::new();
+//! lox.eval("fun square(x) { return x * x; }").unwrap();
+//! assert_eq!(lox.eval("square(3);").unwrap(), Value::Number(9.0));
+//! ```
+//!
+//! The individual phases are public as well, for tools that only need the tokens or the AST.
+
+use std::fmt;
+
+pub mod ast;
+pub mod class;
+pub mod diagnostics;
+pub mod dump;
+pub mod environment;
+pub mod function;
+pub mod interpreter;
+pub mod parser;
+pub mod resolver;
+pub mod scanner;
+pub mod span;
+pub mod value;
+
+use diagnostics::{Diagnostic, Diagnostics};
+use interpreter::{Interpreter, RuntimeError};
+use parser::Parser;
+use resolver::Resolver;
+use scanner::Scanner;
+use span::SourceId;
+use value::Value;
+
+#[derive(Debug)]
+pub enum LoxError {
+    /// Everything the scanner, parser and resolver found wrong with the script.
+    Static(Vec<Diagnostic>),
+    Runtime(RuntimeError),
+}
+
+impl LoxError {
+    /// Static errors are found before the script runs, runtime errors while it does.
+    pub fn is_runtime(&self) -> bool {
+        matches!(self, LoxError::Runtime(_))
+    }
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoxError::Static(diagnostics) => {
+                let lines: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            LoxError::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for LoxError {}
+
+/// An interpreter session, variables and functions defined by one call to [`Lox::eval`]
+/// remain visible to the next.
+#[derive(Default)]
+pub struct Lox {
+    interpreter: Interpreter,
+    /// Every evaluated source, errors may point into any of them
+    sources: Vec<String>,
+}
+
+impl Lox {
+    pub fn new() -> Self {
+        Lox::default()
+    }
+
+    /// Runs `source` and returns the value of its last statement, if that is an
+    /// expression statement, `nil` otherwise.
+    pub fn eval(&mut self, source: &str) -> Result<Value, LoxError> {
+        let mut diagnostics = Diagnostics::new();
+
+        let id = SourceId(self.sources.len());
+        self.sources.push(source.to_string());
+        let tokens = Scanner::new(source).source_id(id).scan_tokens();
+        let statements = Parser::new(tokens, &mut diagnostics).parse();
+        if diagnostics.has_errors() {
+            return Err(LoxError::Static(diagnostics.into_vec()));
+        }
+
+        Resolver::new(&mut diagnostics).resolve(&statements);
+        if diagnostics.has_errors() {
+            return Err(LoxError::Static(diagnostics.into_vec()));
+        }
+
+        self.interpreter
+            .interpret(&statements)
+            .map_err(LoxError::Runtime)
+    }
+
+    /// The source a span points into, to render diagnostics against.
+    pub fn source(&self, id: SourceId) -> Option<&str> {
+        self.sources.get(id.0).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_returns_the_value_of_the_last_expression_statement() {
+        let mut lox = Lox::new();
+        assert_eq!(lox.eval("1 + 2;").unwrap(), Value::Number(3.0));
+        assert_eq!(lox.eval("var a = 1;").unwrap(), Value::Nil);
+        assert_eq!(lox.eval("").unwrap(), Value::Nil);
+    }
+
+    #[test]
+    fn it_keeps_globals_between_evaluations() {
+        let mut lox = Lox::new();
+        lox.eval("var greeting = \"hello\";").unwrap();
+        lox.eval("fun greet(name) { return greeting + \" \" + name; }")
+            .unwrap();
+        assert_eq!(
+            lox.eval("greet(\"lox\");").unwrap(),
+            Value::String(String::from("hello lox"))
+        );
+    }
+
+    #[test]
+    fn it_runs_executable_scripts() {
+        let mut lox = Lox::new();
+        let script = "#!/usr/bin/env rlox\nvar a = 1;\na + 1;";
+        assert_eq!(lox.eval(script).unwrap(), Value::Number(2.0));
+
+        let error = lox.eval("#!/usr/bin/env rlox\n-nil;").unwrap_err();
+        assert_eq!(error.to_string(), "Operand must be a number.\n[line 2]");
+    }
+
+    #[test]
+    fn it_renders_runtime_errors_from_earlier_sources() {
+        let mut lox = Lox::new();
+        lox.eval("fun f() {\n  return -nil;\n}").unwrap();
+        let Err(LoxError::Runtime(error)) = lox.eval("f();") else {
+            panic!("Expected a runtime error");
+        };
+
+        let diagnostic = error.to_diagnostic();
+        let source = lox.source(diagnostic.span.source).unwrap();
+        assert_eq!(
+            diagnostic.render("<stdin>", source),
+            "\
+error: Operand must be a number.
+ --> <stdin>:2:10
+  |
+2 |   return -nil;
+  |          ^
+"
+        );
+    }
+
+    #[test]
+    fn it_returns_structured_errors() {
+        let mut lox = Lox::new();
+        match lox.eval("print ;\nvar @;") {
+            Err(LoxError::Static(diagnostics)) => {
+                let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+                assert_eq!(
+                    messages,
+                    vec![
+                        "[line 1:7] Error at ';': Expect expression.",
+                        "[line 2:5] Error at '@': Expect variable name.",
+                        "[line 2:5] Error: Unexpected character: @",
+                    ]
+                );
+            }
+            result => panic!("Expected static errors, got {:?}", result),
+        }
+
+        let error = lox.eval("{ var a = 1; var a = 2; }").unwrap_err();
+        assert!(matches!(error, LoxError::Static(_)));
+        assert!(!error.is_runtime());
+
+        let error = lox.eval("\n-nil;").unwrap_err();
+        assert!(error.is_runtime());
+        assert_eq!(error.to_string(), "Operand must be a number.\n[line 2]");
+    }
+}
@@ -2,79 +2,82 @@ use std::fs;
 use std::io::{self, BufRead};
 use std::{env, process::exit};
 
-mod scanner;
+use rlox::diagnostics::Diagnostic;
+use rlox::dump::{dump_tokens, Format};
+use rlox::{Lox, LoxError};
+
+const USAGE: &str = "Usage: rlox [script]\n       rlox tokens [--format json|table] script";
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() > 2 {
-        println!("Usage: rlox [script]");
-        exit(64);
-    } else if args.len() == 2 {
-        run_file(&args[1]);
-    } else {
-        run_prompt();
+    match &args[1..] {
+        [command, rest @ ..] if command == "tokens" => print_tokens(rest),
+        [script] => run_file(script),
+        [] => run_prompt(),
+        _ => usage(),
     }
 }
 
+fn usage() -> ! {
+    println!("{}", USAGE);
+    exit(64);
+}
+
+/// `rlox tokens`, prints the tokens of a script without running it.
+fn print_tokens(args: &[String]) {
+    let (format, filename) = match args {
+        [filename] => (Format::Table, filename),
+        [flag, format, filename] if flag == "--format" => match format.parse() {
+            Ok(format) => (format, filename),
+            Err(message) => {
+                eprintln!("{}", message);
+                usage();
+            }
+        },
+        _ => usage(),
+    };
+    let contents = read_script(filename);
+    print!("{}", dump_tokens(&contents, format));
+}
+
+/// Exits with `EX_NOINPUT` if the script can't be read.
+fn read_script(filename: &str) -> String {
+    fs::read_to_string(filename).unwrap_or_else(|error| {
+        eprintln!("Could not read '{}': {}", filename, error);
+        exit(66);
+    })
+}
+
 fn run_file(filename: &str) {
-    let contents =
-        fs::read_to_string(filename).expect("Should have been able to read the script file");
-    run(&contents);
-    if lox::state().has_error() {
-        exit(65);
+    let contents = read_script(filename);
+    let mut lox = Lox::new();
+    if let Err(error) = run(&mut lox, filename, &contents) {
+        exit(if error.is_runtime() { 70 } else { 65 });
     }
 }
 
 fn run_prompt() {
     let stdin = io::stdin();
+    let mut lox = Lox::new();
     for line in stdin.lock().lines() {
         let line = line.expect("Unable to read line from stdin");
-        run(&line);
-        lox::state().reset();
-    }
-}
-
-fn run(script: &str) {
-    if !script.is_empty() {
-        let scanner = &mut scanner::Scanner::new(script);
-        let tokens = scanner.scan_tokens();
-        for token in tokens {
-            println!("{:?}", token);
-        }
+        let _ = run(&mut lox, "<stdin>", &line);
     }
 }
 
-mod lox {
-    use std::sync::{Mutex, MutexGuard};
-
-    #[derive(Debug)]
-    pub struct State {
-        has_error: bool,
-    }
-
-    impl State {
-        pub fn has_error(&self) -> bool {
-            self.has_error
-        }
-
-        pub fn reset(&mut self) {
-            self.has_error = false;
+fn run(lox: &mut Lox, file_name: &str, script: &str) -> Result<(), LoxError> {
+    let result = lox.eval(script).map(|_| ());
+    // runtime errors may come from a function entered on an earlier line of the REPL
+    let render = |diagnostic: &Diagnostic| {
+        let source = lox.source(diagnostic.span.source).unwrap_or_default();
+        diagnostic.render(file_name, source)
+    };
+    result.inspect_err(|error| match error {
+        LoxError::Static(diagnostics) => {
+            for diagnostic in diagnostics {
+                eprintln!("{}", render(diagnostic));
+            }
         }
-    }
-
-    static STATE: Mutex<State> = Mutex::new(State { has_error: false });
-
-    pub fn state() -> MutexGuard<'static, State> {
-        STATE.lock().unwrap()
-    }
-
-    pub fn error(line: usize, message: &str) {
-        report(line, "", message);
-    }
-
-    pub fn report(line: usize, _where: &str, message: &str) {
-        eprintln!("[line {}] Error {}: {}", line, _where, message);
-        let mut state = state();
-        state.has_error = true;
-    }
+        LoxError::Runtime(error) => eprintln!("{}", render(&error.to_diagnostic())),
+    })
 }
@@ -0,0 +1,1074 @@
+use std::borrow::Cow;
+use std::rc::Rc;
+
+use crate::ast::{Depth, Expr, FunctionDecl, Literal, Stmt};
+use crate::diagnostics::{Diagnostic, Diagnostics};
+use crate::scanner::{LiteralType, Token, TokenType, TokenType::*};
+use crate::span::Span;
+
+/// Unwinds the parser up to the next statement boundary, see `synchronize`.
+#[derive(Debug)]
+struct ParseError {
+    diagnostic: Box<Diagnostic>,
+    /// at or right after a token the scanner already reported, see `Parser::error`
+    follows_scanner_error: bool,
+}
+
+impl ParseError {
+    fn with_label(mut self, token: &Token<'static>, message: &str) -> Self {
+        self.diagnostic.with_label(token.span, message);
+        self
+    }
+}
+
+type ParseResult<T> = Result<T, ParseError>;
+
+const MAX_ARGUMENTS: usize = 255;
+
+pub struct Parser<'a> {
+    tokens: Vec<Token<'static>>,
+    current: usize,
+    diagnostics: &'a mut Diagnostics,
+}
+
+impl<'a> Parser<'a> {
+    /// The scanner's errors are reported as the parser gets to them, so all errors come out in
+    /// source order. Tokens that don't end in `Eof` get one appended, right after the last token.
+    pub fn new(mut tokens: Vec<Token<'static>>, diagnostics: &'a mut Diagnostics) -> Self {
+        if tokens.last().is_none_or(|token| token.token_type != Eof) {
+            tokens.push(eof_after(tokens.last()));
+        }
+        Parser {
+            tokens,
+            current: 0,
+            diagnostics,
+        }
+    }
+
+    /// Parses the whole program, recovering from syntax errors at statement boundaries,
+    /// so that all errors are reported in one go. Statements that failed to parse are
+    /// left out of the result.
+    pub fn parse(&mut self) -> Vec<Stmt> {
+        let mut statements = vec![];
+        while !self.at_end() {
+            statements.extend(self.declaration_or_recover(false));
+        }
+        statements
+    }
+
+    /// Reports a failed declaration and skips to the next one, so blocks keep being parsed
+    /// after an error in one of their statements.
+    fn declaration_or_recover(&mut self, in_block: bool) -> Option<Stmt> {
+        match self.declaration() {
+            Ok(statement) => Some(statement),
+            Err(error) => {
+                self.report(error);
+                self.synchronize(in_block);
+                None
+            }
+        }
+    }
+
+    fn declaration(&mut self) -> ParseResult<Stmt> {
+        if self.matches(&[Class]) {
+            return self.class_declaration();
+        }
+        if self.matches(&[Fun]) {
+            return Ok(Stmt::Function(Rc::new(self.function("function")?)));
+        }
+        if self.matches(&[Var]) {
+            return self.var_declaration();
+        }
+        self.statement()
+    }
+
+    fn class_declaration(&mut self) -> ParseResult<Stmt> {
+        let name = self.consume(Identifier, "Expect class name.")?.clone();
+
+        let superclass = if self.matches(&[Less]) {
+            let name = self.consume(Identifier, "Expect superclass name.")?.clone();
+            Some(Expr::Variable {
+                depth: Depth::default(),
+                name,
+            })
+        } else {
+            None
+        };
+
+        self.consume(LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = vec![];
+        while !self.check(&RightBrace) && !self.at_end() {
+            methods.push(Rc::new(self.function("method")?));
+        }
+
+        self.consume(RightBrace, "Expect '}' after class body.")?;
+        Ok(Stmt::Class {
+            name,
+            superclass,
+            methods,
+        })
+    }
+
+    /// `kind` is only used in error messages, to tell functions and methods apart
+    fn function(&mut self, kind: &str) -> ParseResult<FunctionDecl> {
+        let name = self
+            .consume(Identifier, &format!("Expect {} name.", kind))?
+            .clone();
+        self.consume(LeftParen, &format!("Expect '(' after {} name.", kind))?;
+
+        let mut params = vec![];
+        if !self.check(&RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    let error = self.error(
+                        self.peek(),
+                        &format!("Can't have more than {} parameters.", MAX_ARGUMENTS),
+                    );
+                    self.report(error);
+                }
+                params.push(self.consume(Identifier, "Expect parameter name.")?.clone());
+                if !self.matches(&[Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(RightParen, "Expect ')' after parameters.")?;
+
+        self.consume(LeftBrace, &format!("Expect '{{' before {} body.", kind))?;
+        let body = self.block()?;
+
+        Ok(FunctionDecl { name, params, body })
+    }
+
+    fn var_declaration(&mut self) -> ParseResult<Stmt> {
+        let name = self.consume(Identifier, "Expect variable name.")?.clone();
+
+        let initializer = if self.matches(&[Equal]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume(Semicolon, "Expect ';' after variable declaration.")?;
+        Ok(Stmt::Var { name, initializer })
+    }
+
+    fn statement(&mut self) -> ParseResult<Stmt> {
+        if self.matches(&[For]) {
+            return self.for_statement();
+        }
+        if self.matches(&[If]) {
+            return self.if_statement();
+        }
+        if self.matches(&[Print]) {
+            return self.print_statement();
+        }
+        if self.matches(&[Return]) {
+            return self.return_statement();
+        }
+        if self.matches(&[While]) {
+            return self.while_statement();
+        }
+        if self.matches(&[LeftBrace]) {
+            return Ok(Stmt::Block {
+                statements: self.block()?,
+            });
+        }
+        self.expression_statement()
+    }
+
+    /// There is no dedicated for loop in the AST, it is desugared into a while loop:
+    /// `{ initializer; while (condition) { body; increment; } }`
+    fn for_statement(&mut self) -> ParseResult<Stmt> {
+        self.consume(LeftParen, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.matches(&[Semicolon]) {
+            None
+        } else if self.matches(&[Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if self.check(&Semicolon) {
+            Expr::Literal {
+                value: Literal::Bool(true),
+            }
+        } else {
+            self.expression()?
+        };
+        self.consume(Semicolon, "Expect ';' after loop condition.")?;
+
+        let increment = if self.check(&RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(RightParen, "Expect ')' after for clauses.")?;
+
+        let mut body = self.statement()?;
+
+        if let Some(increment) = increment {
+            body = Stmt::Block {
+                statements: vec![
+                    body,
+                    Stmt::Expression {
+                        expression: increment,
+                    },
+                ],
+            };
+        }
+
+        body = Stmt::While {
+            condition,
+            body: Box::new(body),
+        };
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block {
+                statements: vec![initializer, body],
+            };
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> ParseResult<Stmt> {
+        self.consume(LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(RightParen, "Expect ')' after if condition.")?;
+
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.matches(&[Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+
+        Ok(Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn print_statement(&mut self) -> ParseResult<Stmt> {
+        let expression = self.expression()?;
+        self.consume(Semicolon, "Expect ';' after value.")?;
+        Ok(Stmt::Print { expression })
+    }
+
+    fn return_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.previous().clone();
+        let value = if self.check(&Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(Semicolon, "Expect ';' after return value.")?;
+        Ok(Stmt::Return { keyword, value })
+    }
+
+    fn while_statement(&mut self) -> ParseResult<Stmt> {
+        self.consume(LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(RightParen, "Expect ')' after condition.")?;
+        let body = Box::new(self.statement()?);
+
+        Ok(Stmt::While { condition, body })
+    }
+
+    /// Expects the opening brace to be consumed already
+    fn block(&mut self) -> ParseResult<Vec<Stmt>> {
+        let brace = self.previous().clone();
+        let mut statements = vec![];
+
+        while !self.check(&RightBrace) && !self.at_end() {
+            statements.extend(self.declaration_or_recover(true));
+        }
+
+        self.consume(RightBrace, "Expect '}' after block.")
+            .map_err(|error| error.with_label(&brace, "unclosed delimiter"))?;
+        Ok(statements)
+    }
+
+    fn expression_statement(&mut self) -> ParseResult<Stmt> {
+        let expression = self.expression()?;
+        self.consume(Semicolon, "Expect ';' after expression.")?;
+        Ok(Stmt::Expression { expression })
+    }
+
+    fn expression(&mut self) -> ParseResult<Expr> {
+        self.assignment()
+    }
+
+    fn assignment(&mut self) -> ParseResult<Expr> {
+        let expr = self.or()?;
+
+        if self.matches(&[Equal]) {
+            let equals = self.previous().clone();
+            let value = self.assignment()?;
+
+            match expr {
+                Expr::Variable { name, .. } => {
+                    return Ok(Expr::Assign {
+                        depth: Depth::default(),
+                        name,
+                        value: Box::new(value),
+                    });
+                }
+                Expr::Get { object, name } => {
+                    return Ok(Expr::Set {
+                        object,
+                        name,
+                        value: Box::new(value),
+                    });
+                }
+                _ => {}
+            }
+
+            // report, but don't bail out, the parser is not confused here
+            let error = self.error(&equals, "Invalid assignment target.");
+            self.report(error);
+        }
+
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.and()?;
+
+        while self.matches(&[Or]) {
+            let operator = self.previous().clone();
+            let right = self.and()?;
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.equality()?;
+
+        while self.matches(&[And]) {
+            let operator = self.previous().clone();
+            let right = self.equality()?;
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.comparison()?;
+
+        while self.matches(&[BangEqual, EqualEqual]) {
+            let operator = self.previous().clone();
+            let right = self.comparison()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.term()?;
+
+        while self.matches(&[Greater, GreaterEqual, Less, LessEqual]) {
+            let operator = self.previous().clone();
+            let right = self.term()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn term(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.factor()?;
+
+        while self.matches(&[Minus, Plus]) {
+            let operator = self.previous().clone();
+            let right = self.factor()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn factor(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.unary()?;
+
+        while self.matches(&[Slash, Star]) {
+            let operator = self.previous().clone();
+            let right = self.unary()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> ParseResult<Expr> {
+        if self.matches(&[Bang, Minus]) {
+            let operator = self.previous().clone();
+            let right = self.unary()?;
+            return Ok(Expr::Unary {
+                operator,
+                right: Box::new(right),
+            });
+        }
+
+        self.call()
+    }
+
+    fn call(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.matches(&[LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.matches(&[Dot]) {
+                let name = self
+                    .consume(Identifier, "Expect property name after '.'.")?
+                    .clone();
+                expr = Expr::Get {
+                    object: Box::new(expr),
+                    name,
+                };
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> ParseResult<Expr> {
+        let mut arguments = vec![];
+        if !self.check(&RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    let error = self.error(
+                        self.peek(),
+                        &format!("Can't have more than {} arguments.", MAX_ARGUMENTS),
+                    );
+                    self.report(error);
+                }
+                arguments.push(self.expression()?);
+                if !self.matches(&[Comma]) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self
+            .consume(RightParen, "Expect ')' after arguments.")?
+            .clone();
+
+        Ok(Expr::Call {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        })
+    }
+
+    fn primary(&mut self) -> ParseResult<Expr> {
+        if self.matches(&[False]) {
+            return Ok(Expr::Literal {
+                value: Literal::Bool(false),
+            });
+        }
+        if self.matches(&[True]) {
+            return Ok(Expr::Literal {
+                value: Literal::Bool(true),
+            });
+        }
+        if self.matches(&[Nil]) {
+            return Ok(Expr::Literal {
+                value: Literal::Nil,
+            });
+        }
+
+        if self.matches(&[Number, TString]) {
+            let value = match &self.previous().literal {
+                LiteralType::NumberLiteral(n) => Literal::Number(*n),
+                LiteralType::StringLiteral(s) => Literal::String(s.to_string()),
+                LiteralType::Nil | LiteralType::Error(_) => Literal::Nil,
+            };
+            return Ok(Expr::Literal { value });
+        }
+
+        if self.matches(&[Interpolation]) {
+            return self.interpolation();
+        }
+
+        // stands in for whatever could not be scanned, its error is reported already
+        if self.matches(&[Error]) {
+            return Ok(Expr::Literal {
+                value: Literal::Nil,
+            });
+        }
+
+        if self.matches(&[Super]) {
+            let keyword = self.previous().clone();
+            self.consume(Dot, "Expect '.' after 'super'.")?;
+            let method = self
+                .consume(Identifier, "Expect superclass method name.")?
+                .clone();
+            return Ok(Expr::Super {
+                depth: Depth::default(),
+                keyword,
+                method,
+            });
+        }
+
+        if self.matches(&[This]) {
+            return Ok(Expr::This {
+                depth: Depth::default(),
+                keyword: self.previous().clone(),
+            });
+        }
+
+        if self.matches(&[Identifier]) {
+            return Ok(Expr::Variable {
+                depth: Depth::default(),
+                name: self.previous().clone(),
+            });
+        }
+
+        if self.matches(&[LeftParen]) {
+            let paren = self.previous().clone();
+            let expr = self.expression()?;
+            self.consume(RightParen, "Expect ')' after expression.")
+                .map_err(|error| error.with_label(&paren, "unclosed delimiter"))?;
+            return Ok(Expr::Grouping {
+                expression: Box::new(expr),
+            });
+        }
+
+        Err(self.error(self.peek(), "Expect expression."))
+    }
+
+    /// Lowers `"a ${b} c"` to `"a " + str(b) + " c"`, called after the first string part
+    fn interpolation(&mut self) -> ParseResult<Expr> {
+        let start = self.previous().clone();
+        let mut expr = string_part(&start);
+        loop {
+            let embedded = Expr::Stringify {
+                expression: Box::new(self.expression()?),
+            };
+            expr = concatenate(expr, embedded, &start);
+
+            if !self.matches(&[InterpolationMiddle]) {
+                self.consume(
+                    InterpolationEnd,
+                    "Expect '}' after interpolated expression.",
+                )
+                .map_err(|error| error.with_label(&start, "interpolated string starts here"))?;
+            }
+            let part = self.previous().clone();
+            expr = concatenate(expr, string_part(&part), &part);
+            if part.token_type == InterpolationEnd {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn matches(&mut self, types: &[TokenType]) -> bool {
+        for token_type in types {
+            if self.check(token_type) {
+                self.advance();
+                return true;
+            }
+        }
+        false
+    }
+
+    fn consume(&mut self, token_type: TokenType, message: &str) -> ParseResult<&Token<'static>> {
+        if self.check(&token_type) {
+            return Ok(self.advance());
+        }
+        Err(self.error(self.peek(), message))
+    }
+
+    fn check(&self, token_type: &TokenType) -> bool {
+        !self.at_end() && self.peek().token_type == *token_type
+    }
+
+    /// Moves past the current token, reporting its error if the scanner found one.
+    fn advance(&mut self) -> &Token<'static> {
+        if !self.at_end() {
+            if let LiteralType::Error(diagnostic) = &self.tokens[self.current].literal {
+                self.diagnostics.push(Diagnostic::clone(diagnostic));
+            }
+            self.current += 1;
+        }
+        self.previous()
+    }
+
+    fn at_end(&self) -> bool {
+        self.peek().token_type == Eof
+    }
+
+    fn peek(&self) -> &Token<'static> {
+        &self.tokens[self.current]
+    }
+
+    fn previous(&self) -> &Token<'static> {
+        &self.tokens[self.current - 1]
+    }
+
+    /// Errors at the end of the input right after an unterminated string or comment only follow
+    /// from that, e.g. a missing `;`. They are not reported.
+    fn error(&self, token: &Token<'static>, message: &str) -> ParseError {
+        let follows_scanner_error = token.token_type == Eof
+            && self
+                .tokens
+                .len()
+                .checked_sub(2)
+                .is_some_and(|index| self.tokens[index].is_unterminated());
+        ParseError {
+            diagnostic: Box::new(Diagnostic::error_at(token, message)),
+            follows_scanner_error,
+        }
+    }
+
+    fn report(&mut self, error: ParseError) {
+        if !error.follows_scanner_error {
+            self.diagnostics.push(*error.diagnostic);
+        }
+    }
+
+    /// Discards tokens until the start of the next statement, so a single syntax error
+    /// does not cascade into a flood of follow up errors. Inside a block it also stops at the
+    /// `}` closing it, so the block still ends there.
+    fn synchronize(&mut self, in_block: bool) {
+        if in_block && self.check(&RightBrace) {
+            return;
+        }
+        self.advance();
+
+        while !self.at_end() {
+            if self.previous().token_type == Semicolon {
+                return;
+            }
+
+            match self.peek().token_type {
+                Class | Fun | Var | For | If | While | Print | Return => return,
+                RightBrace if in_block => return,
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+    }
+}
+
+fn eof_after(token: Option<&Token<'static>>) -> Token<'static> {
+    let (line, column, span) = token.map_or((1, 1, Span::default()), |token| {
+        let width = token.lexeme.chars().count();
+        let end = token.span.end;
+        let span = Span::new(end, end).in_source(token.span.source);
+        (token.line, token.column + width, span)
+    });
+    Token {
+        token_type: Eof,
+        lexeme: Cow::Borrowed(""),
+        literal: LiteralType::Nil,
+        line,
+        column,
+        span,
+        leading_trivia: vec![],
+        trailing_trivia: vec![],
+    }
+}
+
+fn string_part(token: &Token<'static>) -> Expr {
+    let value = match &token.literal {
+        LiteralType::StringLiteral(value) => value.to_string(),
+        // an invalid escape, reported when the part was consumed
+        _ => String::new(),
+    };
+    Expr::Literal {
+        value: Literal::String(value),
+    }
+}
+
+/// Joins two string expressions with a `+` pointing at `token`
+fn concatenate(left: Expr, right: Expr, token: &Token<'static>) -> Expr {
+    let operator = Token {
+        token_type: Plus,
+        lexeme: Cow::Borrowed("+"),
+        literal: LiteralType::Nil,
+        ..token.clone()
+    };
+    Expr::Binary {
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::assert_eq;
+
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn parse_program(input: &str) -> String {
+        let mut diagnostics = Diagnostics::new();
+        let tokens = Scanner::new(input).scan_tokens();
+        let statements = Parser::new(tokens, &mut diagnostics).parse();
+        if diagnostics.has_errors() {
+            return String::new();
+        }
+        statements
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn it_labels_unclosed_delimiters() {
+        let source = "print (1 +\n 2;";
+        let mut diagnostics = Diagnostics::new();
+        let tokens = Scanner::new(source).scan_tokens();
+        Parser::new(tokens, &mut diagnostics).parse();
+        let rendered: Vec<String> = diagnostics
+            .iter()
+            .map(|d| d.render("script.lox", source))
+            .collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "\
+error: Expect ')' after expression.
+ --> script.lox:2:3
+  |
+1 | print (1 +
+  |       - unclosed delimiter
+2 |  2;
+  |   ^
+"
+            ]
+        );
+    }
+
+    fn parse_errors(input: &str) -> Vec<(usize, String)> {
+        let mut diagnostics = Diagnostics::new();
+        let tokens = Scanner::new(input).scan_tokens();
+        Parser::new(tokens, &mut diagnostics).parse();
+        diagnostics
+            .into_vec()
+            .into_iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.message))
+            .collect()
+    }
+
+    fn parse(input: &str) -> String {
+        parse_program(&format!("print {};", input))
+            .strip_prefix("(print ")
+            .and_then(|s| s.strip_suffix(')'))
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    #[test]
+    fn it_lowers_string_interpolation_to_concatenation() {
+        assert_eq!(
+            parse("\"a${x}b${1 + 2}c\""),
+            "(+ (+ (+ (+ a (str x)) b) (str (+ 1 2))) c)"
+        );
+        assert_eq!(
+            parse_errors("print \"a${x y}\";"),
+            vec![(1, String::from("Expect '}' after interpolated expression."))]
+        );
+    }
+
+    #[test]
+    fn it_rejects_empty_interpolations() {
+        let mut diagnostics = Diagnostics::new();
+        let tokens = Scanner::new("print \"<${}>\";").scan_tokens();
+        Parser::new(tokens, &mut diagnostics).parse();
+        let diagnostics = diagnostics.into_vec();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Expect expression.");
+        assert_eq!(diagnostics[0].column, 11);
+        assert_eq!(
+            parse_errors("print \"a${}b\" \"c\";"),
+            vec![(1, String::from("Expect expression."))]
+        );
+    }
+
+    #[test]
+    fn it_parses_literals() {
+        assert_eq!(parse("123"), "123");
+        assert_eq!(parse("\"foo\""), "foo");
+        assert_eq!(parse("true"), "true");
+        assert_eq!(parse("nil"), "nil");
+    }
+
+    #[test]
+    fn it_respects_precedence() {
+        assert_eq!(parse("1 + 2 * 3"), "(+ 1 (* 2 3))");
+        assert_eq!(parse("1 < 2 == 3 >= 4"), "(== (< 1 2) (>= 3 4))");
+        assert_eq!(parse("-1 * (2 + 3)"), "(* (- 1) (group (+ 2 3)))");
+    }
+
+    #[test]
+    fn it_is_left_associative() {
+        assert_eq!(parse("1 - 2 - 3"), "(- (- 1 2) 3)");
+        assert_eq!(parse("8 / 4 / 2"), "(/ (/ 8 4) 2)");
+    }
+
+    #[test]
+    fn it_nests_unary_operators() {
+        assert_eq!(parse("!!true"), "(! (! true))");
+    }
+
+    #[test]
+    fn it_fails_on_missing_closing_paren() {
+        assert_eq!(parse("(1 + 2"), "");
+    }
+
+    #[test]
+    fn it_parses_assignment_right_associative() {
+        assert_eq!(parse("a = b = 1"), "(= a (= b 1))");
+        assert_eq!(parse("a or b and c"), "(or a (and b c))");
+    }
+
+    #[test]
+    fn it_parses_var_declarations() {
+        assert_eq!(parse_program("var a = 1 + 2;"), "(var a (+ 1 2))");
+        assert_eq!(parse_program("var a;"), "(var a)");
+    }
+
+    #[test]
+    fn it_parses_blocks_and_if_else() {
+        assert_eq!(
+            parse_program("if (a) { print 1; } else print 2;"),
+            "(if a (block (print 1)) (print 2))"
+        );
+        assert_eq!(
+            parse_program("if (a) if (b) print 1; else print 2;"),
+            "(if a (if b (print 1) (print 2)))"
+        );
+    }
+
+    #[test]
+    fn it_desugars_for_into_while() {
+        assert_eq!(
+            parse_program("for (var i = 0; i < 3; i = i + 1) print i;"),
+            "(block (var i 0) (while (< i 3) (block (print i) (; (= i (+ i 1))))))"
+        );
+        assert_eq!(parse_program("for (;;) x;"), "(while true (; x))");
+    }
+
+    #[test]
+    fn it_parses_function_declarations_and_calls() {
+        assert_eq!(
+            parse_program("fun add(a, b) { return a + b; } print add(1, 2)(3);"),
+            "(fun add (a b) (return (+ a b))) (print (call (call add 1 2) 3))"
+        );
+        assert_eq!(parse_program("fun f() { return; }"), "(fun f () (return))");
+    }
+
+    #[test]
+    fn it_parses_classes_and_properties() {
+        assert_eq!(
+            parse_program("class A { init(a) { this.a = a; } get() { return this.a; } }"),
+            "(class A (fun init (a) (; (= (. this a) a))) (fun get () (return (. this a))))"
+        );
+        assert_eq!(
+            parse("a.b(1).c = d.e"),
+            "(= (. (call (. a b) 1) c) (. d e))"
+        );
+    }
+
+    #[test]
+    fn it_parses_superclasses_and_super_calls() {
+        assert_eq!(
+            parse_program("class B < A { m() { return super.m(); } }"),
+            "(class B < A (fun m () (return (call (super m)))))"
+        );
+        assert_eq!(parse_program("class B < { }"), "");
+        assert_eq!(parse_program("super;"), "");
+    }
+
+    #[test]
+    fn it_limits_the_number_of_arguments() {
+        let arguments = vec!["1"; 255].join(", ");
+        assert_ne!(parse(&format!("f({})", arguments)), "");
+
+        // reported, but the parser keeps going
+        assert_eq!(
+            parse_errors(&format!("f({}, 1); 1 +;", arguments)),
+            vec![
+                (1, String::from("Can't have more than 255 arguments.")),
+                (1, String::from("Expect expression."))
+            ]
+        );
+    }
+
+    #[test]
+    fn it_reports_error_tokens_and_parses_around_them() {
+        assert_eq!(
+            parse_errors("var a = 1 @@ + 2;\nprint \"open;"),
+            vec![
+                (1, String::from("Expect ';' after variable declaration.")),
+                (1, String::from("Unexpected characters: @@")),
+                (2, String::from("Unterminated string.")),
+            ]
+        );
+        assert_eq!(
+            parse_errors("var x = \"bad \\q\";\nprint \"${ \"\\q\" } and \\q ${1}\";"),
+            vec![
+                (1, String::from("Invalid escape sequence.")),
+                (2, String::from("Invalid escape sequence.")),
+                (2, String::from("Invalid escape sequence.")),
+            ]
+        );
+        assert_eq!(
+            parse_errors("print @;\n{ print 1 @ }\nprint;"),
+            vec![
+                (1, String::from("Unexpected character: @")),
+                (2, String::from("Expect ';' after value.")),
+                (2, String::from("Unexpected character: @")),
+                (3, String::from("Expect expression.")),
+            ]
+        );
+        assert_eq!(
+            parse_errors("print 1 # + 2;"),
+            vec![
+                (1, String::from("Expect ';' after value.")),
+                (1, String::from("Unexpected character: #")),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_only_suppresses_errors_after_unterminated_tokens() {
+        assert_eq!(
+            parse_errors("print \"x\\q\" 1;"),
+            vec![
+                (1, String::from("Invalid escape sequence.")),
+                (1, String::from("Expect ';' after value.")),
+            ]
+        );
+        assert_eq!(
+            parse_errors("var a = @\nprint a;"),
+            vec![
+                (1, String::from("Unexpected character: @")),
+                (2, String::from("Expect ';' after variable declaration.")),
+            ]
+        );
+        assert_eq!(
+            parse_errors("print 1;\n/* open"),
+            vec![(2, String::from("Unterminated block comment."))]
+        );
+    }
+
+    #[test]
+    fn it_reports_every_syntax_error_in_one_pass() {
+        let program = "
+            var = 1;
+            print 2;
+            var x = ;
+            fun f( { }
+            class A { m() { return 1 } }
+            if (x) print 3;
+            a + b = c;
+        ";
+        assert_eq!(
+            parse_errors(program),
+            vec![
+                (2, String::from("Expect variable name.")),
+                (4, String::from("Expect expression.")),
+                (5, String::from("Expect parameter name.")),
+                (6, String::from("Expect ';' after return value.")),
+                (8, String::from("Invalid assignment target.")),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_recovers_inside_blocks_and_function_bodies() {
+        assert_eq!(
+            parse_errors("fun f() {\n var = 1;\n print 2;\n}\nprint f();"),
+            vec![(2, String::from("Expect variable name."))]
+        );
+        assert_eq!(
+            parse_errors("class A {\n m() {\n  print ;\n }\n n() { return 1 }\n}"),
+            vec![
+                (3, String::from("Expect expression.")),
+                (5, String::from("Expect ';' after return value.")),
+            ]
+        );
+        assert_eq!(
+            parse_errors("{ var = 1 }\n{ print ; print 1 + ; }\nprint 2;"),
+            vec![
+                (1, String::from("Expect variable name.")),
+                (2, String::from("Expect expression.")),
+                (2, String::from("Expect expression.")),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_resynchronizes_at_statement_keywords() {
+        assert_eq!(
+            parse_errors("print (1 var a = 2; print a a; while (true) x;"),
+            vec![
+                (1, String::from("Expect ')' after expression.")),
+                (1, String::from("Expect ';' after value.")),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_accepts_tokens_without_eof() {
+        let mut diagnostics = Diagnostics::new();
+        assert!(Parser::new(vec![], &mut diagnostics).parse().is_empty());
+        assert!(!diagnostics.has_errors());
+
+        let mut tokens = Scanner::new("print 1").scan_tokens();
+        tokens.pop();
+        Parser::new(tokens, &mut diagnostics).parse();
+        let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec!["[line 1:8] Error at end: Expect ';' after value."]
+        );
+    }
+
+    #[test]
+    fn it_fails_on_missing_semicolon() {
+        assert_eq!(parse_program("print 1"), "");
+    }
+}
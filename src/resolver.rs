@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+
+use crate::ast::{Depth, Expr, FunctionDecl, Stmt};
+use crate::diagnostics::{Diagnostic, Diagnostics};
+use crate::scanner::Token;
+use crate::span::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+struct Binding {
+    /// false while the variable's initializer is being resolved
+    defined: bool,
+    declared_at: Span,
+}
+
+/// Walks the AST once before it is run, recording on each local variable reference
+/// how many scopes up it binds to. Globals are left unresolved.
+pub struct Resolver<'a> {
+    scopes: Vec<HashMap<String, Binding>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    diagnostics: &'a mut Diagnostics,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(diagnostics: &'a mut Diagnostics) -> Self {
+        Resolver {
+            scopes: vec![],
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            diagnostics,
+        }
+    }
+
+    /// Resolves all statements, reporting every error found along the way.
+    pub fn resolve(&mut self, statements: &[Stmt]) {
+        self.resolve_statements(statements);
+    }
+
+    fn resolve_statements(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.resolve_statement(statement);
+        }
+    }
+
+    fn resolve_statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block { statements } => {
+                self.begin_scope();
+                self.resolve_statements(statements);
+                self.end_scope();
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let enclosing_class = self.current_class;
+                self.current_class = ClassType::Class;
+
+                self.declare(name);
+                self.define(name);
+
+                if let Some(superclass) = superclass {
+                    match superclass {
+                        Expr::Variable {
+                            name: superclass_name,
+                            ..
+                        } if superclass_name.lexeme == name.lexeme => {
+                            self.error(superclass_name, "A class can't inherit from itself.");
+                        }
+                        _ => {}
+                    }
+                    self.current_class = ClassType::Subclass;
+                    self.resolve_expression(superclass);
+
+                    // methods of a subclass close over an extra scope holding `super`
+                    self.begin_scope();
+                    self.define_keyword("super");
+                }
+
+                self.begin_scope();
+                self.define_keyword("this");
+                for method in methods {
+                    let function_type = if method.name.lexeme == "init" {
+                        FunctionType::Initializer
+                    } else {
+                        FunctionType::Method
+                    };
+                    self.resolve_function(method, function_type);
+                }
+                self.end_scope();
+
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+
+                self.current_class = enclosing_class;
+            }
+            Stmt::Expression { expression } => self.resolve_expression(expression),
+            Stmt::Function(declaration) => {
+                self.declare(&declaration.name);
+                self.define(&declaration.name);
+                self.resolve_function(declaration, FunctionType::Function);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_statement(else_branch);
+                }
+            }
+            Stmt::Print { expression } => self.resolve_expression(expression),
+            Stmt::Return { keyword, value } => {
+                if self.current_function == FunctionType::None {
+                    self.error(keyword, "Can't return from top-level code.");
+                }
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        self.error(keyword, "Can't return a value from an initializer.");
+                    }
+                    self.resolve_expression(value);
+                }
+            }
+            Stmt::Var { name, initializer } => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expression(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::While { condition, body } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(body);
+            }
+        }
+    }
+
+    fn resolve_expression(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Assign { depth, name, value } => {
+                self.resolve_expression(value);
+                self.resolve_local(depth, name);
+            }
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.resolve_expression(left);
+                self.resolve_expression(right);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.resolve_expression(callee);
+                for argument in arguments {
+                    self.resolve_expression(argument);
+                }
+            }
+            Expr::Get { object, .. } => self.resolve_expression(object),
+            Expr::Grouping { expression } => self.resolve_expression(expression),
+            Expr::Literal { .. } => {}
+            Expr::Set { object, value, .. } => {
+                self.resolve_expression(value);
+                self.resolve_expression(object);
+            }
+            Expr::Stringify { expression } => self.resolve_expression(expression),
+            Expr::Super { depth, keyword, .. } => {
+                match self.current_class {
+                    ClassType::None => {
+                        self.error(keyword, "Can't use 'super' outside of a class.");
+                        return;
+                    }
+                    ClassType::Class => {
+                        self.error(keyword, "Can't use 'super' in a class with no superclass.");
+                        return;
+                    }
+                    ClassType::Subclass => {}
+                }
+                self.resolve_local(depth, keyword);
+            }
+            Expr::This { depth, keyword } => {
+                if self.current_class == ClassType::None {
+                    self.error(keyword, "Can't use 'this' outside of a class.");
+                    return;
+                }
+                self.resolve_local(depth, keyword);
+            }
+            Expr::Unary { right, .. } => self.resolve_expression(right),
+            Expr::Variable { depth, name } => {
+                let binding = self
+                    .scopes
+                    .last()
+                    .and_then(|scope| scope.get(name.lexeme.as_ref()));
+                if let Some(Binding {
+                    defined: false,
+                    declared_at,
+                }) = binding
+                {
+                    let declared_at = *declared_at;
+                    self.error(name, "Can't read local variable in its own initializer.")
+                        .with_label(declared_at, "variable declared here");
+                }
+                self.resolve_local(depth, name);
+            }
+        }
+    }
+
+    fn resolve_function(&mut self, declaration: &FunctionDecl, function_type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+
+        self.begin_scope();
+        for param in &declaration.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_statements(&declaration.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    fn resolve_local(&mut self, depth: &Depth, name: &Token) {
+        let scope = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(name.lexeme.as_ref()));
+        if let Some(scope) = scope {
+            depth.resolve(scope);
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if let Some(previous) = scope.get(name.lexeme.as_ref()) {
+            let declared_at = previous.declared_at;
+            self.error(name, "Already a variable with this name in this scope.")
+                .with_label(declared_at, "previously declared here");
+            return;
+        }
+        scope.insert(
+            name.lexeme.to_string(),
+            Binding {
+                defined: false,
+                declared_at: name.span,
+            },
+        );
+    }
+
+    fn define(&mut self, name: &Token) {
+        let binding = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(name.lexeme.as_ref()));
+        if let Some(binding) = binding {
+            binding.defined = true;
+        }
+    }
+
+    /// Defines `this` or `super` in the innermost scope, they are never declared by the user.
+    fn define_keyword(&mut self, keyword: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            let binding = Binding {
+                defined: true,
+                declared_at: Span::default(),
+            };
+            scope.insert(String::from(keyword), binding);
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: &str) -> &mut Diagnostic {
+        self.diagnostics.error_at(token, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn resolve(input: &str) -> Result<(), Vec<String>> {
+        let mut diagnostics = Diagnostics::new();
+        let tokens = Scanner::new(input).scan_tokens();
+        let statements = Parser::new(tokens, &mut diagnostics).parse();
+        assert!(!diagnostics.has_errors(), "Should parse");
+
+        Resolver::new(&mut diagnostics).resolve(&statements);
+        if diagnostics.has_errors() {
+            Err(diagnostics.iter().map(|d| d.to_string()).collect())
+        } else {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn it_accepts_valid_programs() {
+        assert!(resolve("var a = 1; { var b = a; fun f(c) { return b + c; } }").is_ok());
+        assert!(resolve("var a = 1; var a = 2;").is_ok());
+    }
+
+    #[test]
+    fn it_records_scope_depths_on_the_ast() {
+        let mut diagnostics = Diagnostics::new();
+        let tokens = Scanner::new("{ var a; { a; } } a;").scan_tokens();
+        let statements = Parser::new(tokens, &mut diagnostics).parse();
+        Resolver::new(&mut diagnostics).resolve(&statements);
+
+        let depth = |statement: &Stmt| match statement {
+            Stmt::Expression {
+                expression: Expr::Variable { depth, .. },
+            } => depth.get(),
+            statement => panic!("Expected a variable, got {}", statement),
+        };
+        let Stmt::Block { statements: outer } = &statements[0] else {
+            panic!("Expected a block");
+        };
+        let Stmt::Block { statements: inner } = &outer[1] else {
+            panic!("Expected a block");
+        };
+        assert_eq!(depth(&inner[0]), Some(1));
+        assert_eq!(depth(&statements[1]), None);
+    }
+
+    #[test]
+    fn it_rejects_reading_a_local_in_its_own_initializer() {
+        assert_eq!(
+            resolve("{ var a = a; }"),
+            Err(vec![String::from(
+                "[line 1:11] Error at 'a': Can't read local variable in its own initializer."
+            )])
+        );
+    }
+
+    #[test]
+    fn it_labels_where_a_redeclared_variable_was_declared() {
+        let source = "{\n  var a = 1;\n  var a = 2;\n}";
+        let mut diagnostics = Diagnostics::new();
+        let tokens = Scanner::new(source).scan_tokens();
+        let statements = Parser::new(tokens, &mut diagnostics).parse();
+        Resolver::new(&mut diagnostics).resolve(&statements);
+
+        let rendered: Vec<String> = diagnostics
+            .iter()
+            .map(|d| d.render("script.lox", source))
+            .collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "\
+error: Already a variable with this name in this scope.
+ --> script.lox:3:7
+  |
+2 |   var a = 1;
+  |       - previously declared here
+3 |   var a = 2;
+  |       ^
+"
+            ]
+        );
+    }
+
+    #[test]
+    fn it_rejects_redeclaring_a_local_in_the_same_scope() {
+        assert!(resolve("{ var a = 1; var a = 2; }").is_err());
+        assert!(resolve("fun f(a, a) {}").is_err());
+    }
+
+    #[test]
+    fn it_rejects_top_level_return() {
+        assert!(resolve("return 1;").is_err());
+        assert!(resolve("fun f() { return 1; }").is_ok());
+    }
+
+    #[test]
+    fn it_rejects_this_outside_of_classes() {
+        assert!(resolve("print this;").is_err());
+        assert!(resolve("fun f() { return this; }").is_err());
+        assert!(resolve("class A { m() { fun f() { return this; } return f; } }").is_ok());
+    }
+
+    #[test]
+    fn it_rejects_returning_a_value_from_an_initializer() {
+        assert!(resolve("class A { init() { return 1; } }").is_err());
+        assert!(resolve("class A { init() { return; } }").is_ok());
+        assert!(resolve("class A { other() { return 1; } }").is_ok());
+    }
+
+    #[test]
+    fn it_rejects_a_class_inheriting_from_itself() {
+        assert!(resolve("class A < A {}").is_err());
+        assert!(resolve("class A {} class B < A {}").is_ok());
+    }
+
+    #[test]
+    fn it_rejects_super_outside_of_subclasses() {
+        assert!(resolve("fun f() { super.m(); }").is_err());
+        assert!(resolve("class A { m() { super.m(); } }").is_err());
+        assert!(resolve("class A {} class B < A { m() { super.m(); } }").is_ok());
+    }
+}
@@ -1,7 +1,10 @@
 use core::{cmp::PartialEq, prelude::v1::derive};
+use std::borrow::Cow;
 
-use crate::lox;
+use crate::diagnostics::Diagnostic;
+use crate::span::{SourceId, Span};
 use phf::phf_map;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
 use TokenType::*;
 
 static KEYWORDS: phf::Map<&'static str, TokenType> = phf_map! {
@@ -23,132 +26,258 @@ static KEYWORDS: phf::Map<&'static str, TokenType> = phf_map! {
     "while" => While
 };
 
-pub struct Scanner {
-    source: String,
-    chars: Vec<char>,
+const FORM_FEED: char = '\u{c}';
+const BYTE_ORDER_MARK: char = '\u{feff}';
+
+/// Scans UTF-8 source text, all positions are byte offsets into `source`.
+///
+/// Tokens are produced lazily through `Iterator` and borrow their lexemes from the source,
+/// the stream always ends with a single `Eof` token. Input that can't be scanned turns into
+/// `Error` tokens, which carry the diagnostic to report. Strings with invalid escapes keep
+/// their token type, so the parser still sees a string, and carry the diagnostic instead of
+/// their value.
+///
+/// In `lossless` mode tokens also carry the whitespace and comments around them, so the source
+/// can be reproduced from the tokens byte for byte.
+pub struct Scanner<'src> {
+    source: &'src str,
+    // tags the spans, see `Scanner::source_id`
+    source_id: SourceId,
     start: usize,
     current: usize,
     line: usize,
-    result: Vec<Token>,
+    // where the current line begins, to derive columns from
+    line_start: usize,
+    // the last offset a column was computed for and that column, on the current line
+    last_column: (usize, usize),
+    // position of the token currently being scanned, tokens may span several lines
+    start_line: usize,
+    start_column: usize,
+    // the token found by the last call to `scan_token`, if any
+    scanned: Option<Token<'src>>,
+    finished: bool,
+    lossless: bool,
+    // trivia seen since the last token, leading trivia of the next one
+    trivia: Vec<Trivia<'src>>,
+    // one entry per string interpolation being scanned, counting the braces opened inside it
+    interpolations: Vec<usize>,
+    // the first bad escape in the string being scanned, which turns the string into an error
+    invalid_escape: Option<Diagnostic>,
 }
 
-impl Scanner {
-    pub fn new(input: &str) -> Self {
+impl<'src> Scanner<'src> {
+    pub fn new(source: &'src str) -> Self {
         Scanner {
-            source: String::from(input),
-            chars: input.chars().collect(),
+            source,
+            source_id: SourceId::default(),
             start: 0,
             current: 0,
             line: 1,
-            result: vec![],
-        }
-    }
-
-    pub fn scan_tokens(&mut self) -> Vec<Token> {
-        while !self.at_end() {
-            self.start = self.current;
-            let char = self.advance();
-            match char {
-                '(' => self.append_token(LeftParen),
-                ')' => self.append_token(RightParen),
-                '{' => self.append_token(LeftBrace),
-                '}' => self.append_token(RightBrace),
-                ',' => self.append_token(Comma),
-                '.' => self.append_token(Dot),
-                '-' => self.append_token(Minus),
-                '+' => self.append_token(Plus),
-                ';' => self.append_token(Semicolon),
-                '*' => self.append_token(Star),
-                '!' => {
-                    if self.next_is('=') {
-                        self.append_token(BangEqual);
-                    } else {
-                        self.append_token(Bang);
-                    }
+            line_start: 0,
+            last_column: (0, 1),
+            start_line: 1,
+            start_column: 1,
+            scanned: None,
+            finished: false,
+            lossless: false,
+            trivia: vec![],
+            interpolations: vec![],
+            invalid_escape: None,
+        }
+    }
+
+    /// Keeps trivia on the tokens, identifiers are left as written instead of being normalized.
+    pub fn lossless(mut self) -> Self {
+        self.lossless = true;
+        self
+    }
+
+    /// Tags all spans with `id`, for sessions that keep several sources around.
+    pub fn source_id(mut self, id: SourceId) -> Self {
+        self.source_id = id;
+        self
+    }
+
+    /// Scans the whole source into tokens that no longer borrow from it, as the parser needs.
+    pub fn scan_tokens(self) -> Vec<Token<'static>> {
+        self.map(Token::into_owned).collect()
+    }
+
+    /// Consumes one lexeme, which may or may not turn out to be a token.
+    fn scan_token(&mut self) {
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column(self.start);
+        let char = self.advance();
+        match char {
+            '(' => self.append_token(LeftParen),
+            ')' => self.append_token(RightParen),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
                 }
-                '=' => {
-                    if self.next_is('=') {
-                        self.append_token(EqualEqual);
-                    } else {
-                        self.append_token(Equal);
-                    }
+                self.append_token(LeftBrace);
+            }
+            '}' => match self.interpolations.last_mut() {
+                // closes the embedded expression, the string continues after it
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.consume_string(true);
                 }
-                '<' => {
-                    if self.next_is('=') {
-                        self.append_token(LessEqual);
-                    } else {
-                        self.append_token(Less);
-                    }
+                Some(depth) => {
+                    *depth -= 1;
+                    self.append_token(RightBrace);
                 }
-                '>' => {
-                    if self.next_is('=') {
-                        self.append_token(GreaterEqual);
-                    } else {
-                        self.append_token(Greater);
-                    }
+                None => self.append_token(RightBrace),
+            },
+            ',' => self.append_token(Comma),
+            '.' => self.append_token(Dot),
+            '-' => self.append_token(Minus),
+            '+' => self.append_token(Plus),
+            ';' => self.append_token(Semicolon),
+            '*' => self.append_token(Star),
+            '!' => {
+                if self.next_is('=') {
+                    self.append_token(BangEqual);
+                } else {
+                    self.append_token(Bang);
                 }
-                '/' => {
-                    if self.next_is('/') {
-                        while self.peek() != '\n' && !self.at_end() {
-                            // keep eating character until the end of the line
-                            self.advance();
-                        }
-                    } else {
-                        self.append_token(Slash);
-                    }
+            }
+            '=' => {
+                if self.next_is('=') {
+                    self.append_token(EqualEqual);
+                } else {
+                    self.append_token(Equal);
                 }
-                '0'..='9' => self.consume_number(),
-                '"' => self.consume_string(),
-                '\n' => self.line += 1,
-                '\t' | ' ' => {}
-                c => {
-                    if self.is_alpha(c) {
-                        self.consume_identifier();
-                    } else {
-                        let message = format!("Unexpected character: {}", c);
-                        lox::error(self.line, &message);
-                    }
+            }
+            '<' => {
+                if self.next_is('=') {
+                    self.append_token(LessEqual);
+                } else {
+                    self.append_token(Less);
+                }
+            }
+            '>' => {
+                if self.next_is('=') {
+                    self.append_token(GreaterEqual);
+                } else {
+                    self.append_token(Greater);
+                }
+            }
+            '/' => {
+                if self.next_is('/') {
+                    self.consume_line();
+                } else if self.next_is('*') {
+                    self.consume_block_comment();
+                } else {
+                    self.append_token(Slash);
+                }
+            }
+            '0'..='9' => self.consume_number(),
+            '"' => self.consume_string(false),
+            'r' if self.peek() == '"' => {
+                self.advance();
+                self.consume_raw_string();
+            }
+            '\n' => self.newline(),
+            '\r' => {
+                // a lone `\r` ends a line as well
+                self.next_is('\n');
+                self.newline();
+            }
+            '\t' | ' ' | FORM_FEED => {}
+            // editors hide the byte order mark, so columns on the first line start after it
+            BYTE_ORDER_MARK if self.start == 0 => self.start_line_at(self.current),
+            // `#!/usr/bin/env rlox`, only as the very first thing in an executable script
+            '#' if self.line == 1 && self.start == self.line_start && self.peek() == '!' => {
+                self.consume_line();
+            }
+            c => {
+                if self.is_alpha(c) {
+                    self.consume_identifier();
+                } else {
+                    self.consume_unexpected();
                 }
             }
         }
+    }
 
-        self.result.push(Token {
-            token_type: TokenType::Eof,
-            lexeme: String::from(""),
-            literal: LiteralType::Nil,
-            line: self.line,
-        });
+    /// Skips the rest of the line, leaving the line break to be scanned next.
+    fn consume_line(&mut self) {
+        while !matches!(self.peek(), '\n' | '\r') && !self.at_end() {
+            self.advance();
+        }
+    }
+
+    /// Call after consuming a line break
+    fn newline(&mut self) {
+        self.line += 1;
+        self.start_line_at(self.current);
+    }
 
-        self.result.clone()
+    fn start_line_at(&mut self, offset: usize) {
+        self.line_start = offset;
+        self.last_column = (offset, 1);
+    }
+
+    /// Call after consuming `c` inside a token, counts `\r\n` as a single line break
+    fn track_newline(&mut self, c: char) {
+        if c == '\n' || (c == '\r' && self.peek() != '\n') {
+            self.newline();
+        }
+    }
+
+    /// Counts characters from the last column computed, so long lines stay linear.
+    fn column(&mut self, offset: usize) -> usize {
+        let (from, column) = match self.last_column {
+            (last, column) if last <= offset => (last, column),
+            _ => (self.line_start, 1),
+        };
+        let column = column + self.source[from..offset].chars().count();
+        self.last_column = (offset, column);
+        column
+    }
+
+    fn span(&self, start: usize, end: usize) -> Span {
+        Span::new(start, end).in_source(self.source_id)
+    }
+
+    /// Turns the lexeme currently being scanned into an error token
+    fn error(&mut self, message: &str) {
+        let span = self.span(self.start, self.current);
+        let diagnostic = Diagnostic::error(span, self.start_line, self.start_column, message);
+        self.append_token_literal(Error, LiteralType::Error(Box::new(diagnostic)));
+    }
+
+    /// Remembers a bad escape from `start` to the current position, unless there already was one
+    fn invalid_escape(&mut self, start: usize, message: &str) {
+        if self.invalid_escape.is_none() {
+            let span = self.span(start, self.current);
+            let diagnostic = Diagnostic::error(span, self.line, self.column(start), message);
+            self.invalid_escape = Some(diagnostic);
+        }
     }
 
     fn next_is(&mut self, c: char) -> bool {
-        if self.at_end() || self.chars[self.current] != c {
+        if self.at_end() || self.peek() != c {
             false
         } else {
-            self.current += 1;
+            self.current += c.len_utf8();
             true
         }
     }
 
     fn peek(&self) -> char {
-        if self.at_end() {
-            '\0'
-        } else {
-            self.chars[self.current]
-        }
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
-        }
-        self.chars[self.current + 1]
+        self.source[self.current..].chars().nth(1).unwrap_or('\0')
     }
 
     fn advance(&mut self) -> char {
-        let result = self.chars[self.current];
-        self.current += 1;
+        let result = self.peek();
+        self.current += result.len_utf8();
         result
     }
 
@@ -156,42 +285,228 @@ impl Scanner {
         self.current >= self.source.len()
     }
 
-    fn consume_string(&mut self) {
-        while self.peek() != '"' && !self.at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+    /// Block comments nest, so commenting out code that contains one keeps working
+    fn consume_block_comment(&mut self) {
+        let mut depth = 1;
+        while depth > 0 {
+            if self.at_end() {
+                // reported where the comment was opened, the end of the file is no help
+                self.error("Unterminated block comment.");
+                return;
+            }
+            match self.advance() {
+                '/' if self.next_is('*') => depth += 1,
+                '*' if self.next_is('/') => depth -= 1,
+                c => self.track_newline(c),
+            }
+        }
+    }
+
+    /// Scans a string literal, or with `continued` the rest of one after an embedded expression.
+    fn consume_string(&mut self, continued: bool) {
+        let source = self.source;
+        let content_start = self.current;
+        // only strings containing escapes need their own copy of the value
+        let mut escaped: Option<String> = None;
+        let token_type = loop {
+            if self.at_end() {
+                self.invalid_escape = None;
+                self.error("Unterminated string.");
+                return;
             }
+            let offset = self.current;
+            match self.advance() {
+                '"' if continued => break InterpolationEnd,
+                '"' => break TString,
+                '$' if self.next_is('{') => {
+                    self.interpolations.push(0);
+                    break if continued {
+                        InterpolationMiddle
+                    } else {
+                        Interpolation
+                    };
+                }
+                '\\' => {
+                    let value =
+                        escaped.get_or_insert_with(|| source[content_start..offset].to_string());
+                    if let Some(c) = self.consume_escape() {
+                        value.push(c);
+                    }
+                }
+                c => {
+                    self.track_newline(c);
+                    if let Some(value) = &mut escaped {
+                        value.push(c);
+                    }
+                }
+            }
+        };
+
+        if let Some(diagnostic) = self.invalid_escape.take() {
+            self.append_token_literal(token_type, LiteralType::Error(Box::new(diagnostic)));
+            return;
+        }
+
+        let content_end = self.current
+            - if matches!(token_type, TString | InterpolationEnd) {
+                1
+            } else {
+                2
+            };
+        let value = match escaped {
+            Some(value) => Cow::Owned(value),
+            None => Cow::Borrowed(&source[content_start..content_end]),
+        };
+        self.append_token_literal(token_type, LiteralType::StringLiteral(value));
+    }
+
+    /// Call after consuming the backslash, returns `None` for invalid escapes after recording them
+    fn consume_escape(&mut self) -> Option<char> {
+        let start = self.current - 1;
+        if self.at_end() {
+            return None;
+        }
+        let c = match self.advance() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '\\' => '\\',
+            '"' => '"',
+            '0' => '\0',
+            '$' => '$',
+            'u' => return self.consume_unicode_escape(start),
+            c @ ('\n' | '\r') => {
+                self.invalid_escape(start, "Invalid escape sequence.");
+                self.track_newline(c);
+                return None;
+            }
+            _ => {
+                self.invalid_escape(start, "Invalid escape sequence.");
+                return None;
+            }
+        };
+        Some(c)
+    }
+
+    /// Reads the `{XXXX}` part of a `\u{XXXX}` escape, with 1 to 6 hex digits
+    fn consume_unicode_escape(&mut self, start: usize) -> Option<char> {
+        if !self.next_is('{') {
+            self.invalid_escape(start, "Invalid unicode escape.");
+            return None;
+        }
+        let digits_start = self.current;
+        while self.peek().is_ascii_hexdigit() {
             self.advance();
         }
+        let digits = &self.source[digits_start..self.current];
+        if !self.next_is('}') || digits.is_empty() || digits.len() > 6 {
+            self.invalid_escape(start, "Invalid unicode escape.");
+            return None;
+        }
+        let c = u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32);
+        if c.is_none() {
+            self.invalid_escape(start, "Invalid unicode escape.");
+        }
+        c
+    }
+
+    /// `r"..."` strings take their content as it is written, backslashes included
+    fn consume_raw_string(&mut self) {
+        while self.peek() != '"' && !self.at_end() {
+            let c = self.advance();
+            self.track_newline(c);
+        }
 
         if self.at_end() {
-            lox::error(self.line, "Unterminated string.");
+            self.error("Unterminated string.");
             return;
         }
 
         self.advance();
-        let value = &self.source[self.start + 1..self.current - 1];
-        self.append_token_literal(TString, LiteralType::StringLiteral(String::from(value)));
+        let value = &self.source[self.start + 2..self.current - 1];
+        self.append_token_literal(TString, LiteralType::StringLiteral(Cow::Borrowed(value)));
     }
 
     fn consume_number(&mut self) {
-        while self.is_digit(self.peek()) {
-            self.advance();
+        if &self.source[self.start..self.current] == "0" {
+            let radix = match self.peek() {
+                'x' => Some(16),
+                'o' => Some(8),
+                'b' => Some(2),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                self.advance();
+                self.consume_radix_number(radix);
+                return;
+            }
         }
 
+        self.consume_digits();
         if self.peek() == '.' && self.is_digit(self.peek_next()) {
             //consume the .
             self.advance();
-
-            while self.is_digit(self.peek()) {
+            self.consume_digits();
+        }
+        if matches!(self.peek(), 'e' | 'E') {
+            self.advance();
+            if matches!(self.peek(), '+' | '-') {
                 self.advance();
             }
+            if !self.is_digit(self.peek()) {
+                self.error("Expect digits in the exponent.");
+                return;
+            }
+            self.consume_digits();
         }
+
         let value = &self.source[self.start..self.current];
-        let number: f32 = value
-            .parse::<f32>()
-            .expect("Could not convert value to f32");
-        self.append_token_literal(Number, LiteralType::NumberLiteral(number));
+        if !separators_between_digits(value, |c| c.is_ascii_digit()) {
+            self.error("Digit separators are only allowed between digits.");
+            return;
+        }
+        match value.replace('_', "").parse::<f64>() {
+            // e.g. `1e400`, which would silently turn into infinity
+            Ok(number) if number.is_infinite() => self.error("Number literal is too large."),
+            Ok(number) => self.append_token_literal(Number, LiteralType::NumberLiteral(number)),
+            Err(_) => self.error("Invalid number literal."),
+        }
+    }
+
+    /// Call after consuming a `0x`, `0o` or `0b` prefix
+    fn consume_radix_number(&mut self, radix: u32) {
+        // take everything that could belong to the literal, so bad digits are reported as part of it
+        while self.is_alpha_numeric(self.peek()) {
+            self.advance();
+        }
+
+        let digits = &self.source[self.start + 2..self.current];
+        let valid = separators_between_digits(digits, |c| c.is_digit(radix))
+            && !digits.is_empty()
+            && digits.chars().all(|c| c == '_' || c.is_digit(radix));
+        let number = u64::from_str_radix(&digits.replace('_', ""), radix);
+        match number {
+            Ok(number) if valid => {
+                self.append_token_literal(Number, LiteralType::NumberLiteral(number as f64))
+            }
+            _ => {
+                let kind = match radix {
+                    16 => "hexadecimal",
+                    8 => "octal",
+                    _ => "binary",
+                };
+                self.error(&format!("Invalid {} literal.", kind));
+            }
+        }
+    }
+
+    /// Digits may be grouped with `_`, as in `1_000_000`
+    fn consume_digits(&mut self) {
+        while self.is_digit(self.peek()) || self.peek() == '_' {
+            self.advance();
+        }
     }
 
     fn consume_identifier(&mut self) {
@@ -199,43 +514,226 @@ impl Scanner {
             self.advance();
         }
 
+        // keywords have to be spelled exactly, only user identifiers are normalized
         let value = &self.source[self.start..self.current];
-        self.append_token(KEYWORDS.get(value).unwrap_or(&Identifier).clone());
+        match KEYWORDS.get(value) {
+            Some(keyword) => self.append_token(keyword.clone()),
+            // so that visually identical names written differently refer to the same variable
+            None if !self.lossless && !is_nfc(value) => {
+                let name: String = value.nfc().collect();
+                self.push_token(Identifier, Cow::Owned(name), LiteralType::Nil);
+            }
+            None => self.append_token(Identifier),
+        }
+    }
+
+    /// Takes the whole run of characters no token can start with, to report them at once
+    fn consume_unexpected(&mut self) {
+        while !self.at_end() && self.is_unexpected(self.peek()) {
+            self.advance();
+        }
+
+        let text = &self.source[self.start..self.current];
+        let message = if text.chars().count() == 1 {
+            format!("Unexpected character: {}", text)
+        } else {
+            format!("Unexpected characters: {}", text)
+        };
+        self.error(&message);
+    }
+
+    fn is_unexpected(&self, c: char) -> bool {
+        let starts_token = "(){},.-+;*!=<>/\"\n\r\t ".contains(c) || c == FORM_FEED;
+        !(self.is_alpha(c) || self.is_digit(c) || starts_token)
     }
 
     fn is_digit(&self, c: char) -> bool {
         c.is_ascii_digit()
     }
 
+    /// Identifiers follow UAX #31, with `_` allowed at the start as well
     fn is_alpha(&self, c: char) -> bool {
-        matches!(c, 'a'..='z' | 'A'..='Z' | '_')
+        c == '_' || unicode_ident::is_xid_start(c)
     }
 
     fn is_alpha_numeric(&self, c: char) -> bool {
-        self.is_digit(c) || self.is_alpha(c)
+        unicode_ident::is_xid_continue(c)
     }
 
     fn append_token(&mut self, token_type: TokenType) {
         self.append_token_literal(token_type, LiteralType::Nil);
     }
 
-    fn append_token_literal(&mut self, token_type: TokenType, literal: LiteralType) {
-        let token = Token {
+    fn append_token_literal(&mut self, token_type: TokenType, literal: LiteralType<'src>) {
+        let lexeme = Cow::Borrowed(&self.source[self.start..self.current]);
+        self.push_token(token_type, lexeme, literal);
+    }
+
+    fn push_token(
+        &mut self,
+        token_type: TokenType,
+        lexeme: Cow<'src, str>,
+        literal: LiteralType<'src>,
+    ) {
+        self.scanned = Some(Token {
             token_type,
-            lexeme: String::from(&self.source[self.start..self.current]),
+            lexeme,
             literal,
+            line: self.start_line,
+            column: self.start_column,
+            span: self.span(self.start, self.current),
+            leading_trivia: vec![],
+            trailing_trivia: vec![],
+        });
+    }
+
+    /// Call after scanning something that did not turn out to be a token
+    fn push_trivia(&self, trivia: &mut Vec<Trivia<'src>>) {
+        let text = &self.source[self.start..self.current];
+        let kind = TriviaKind::of(text);
+        match trivia.last_mut() {
+            // runs of spaces and tabs are kept together
+            Some(last) if last.kind == TriviaKind::Whitespace && kind == TriviaKind::Whitespace => {
+                last.span.end = self.current;
+                last.text = Cow::Borrowed(&self.source[last.span.start..self.current]);
+            }
+            _ => trivia.push(Trivia {
+                kind,
+                text: Cow::Borrowed(text),
+                span: self.span(self.start, self.current),
+            }),
+        }
+    }
+
+    /// Everything up to the end of the line belongs to the token before it. Stops early at an
+    /// error, like an unterminated block comment, leaving the error token for `next` to return.
+    fn scan_trailing_trivia(&mut self) -> Vec<Trivia<'src>> {
+        let mut trivia = vec![];
+        loop {
+            let comment_ahead = self.peek() == '/' && matches!(self.peek_next(), '/' | '*');
+            let whitespace_ahead = matches!(self.peek(), ' ' | '\t' | FORM_FEED);
+            if self.at_end() || !(whitespace_ahead || comment_ahead) {
+                return trivia;
+            }
+            self.scan_token();
+            if self.scanned.is_some() {
+                return trivia;
+            }
+            self.push_trivia(&mut trivia);
+        }
+    }
+}
+
+impl<'src> Iterator for Scanner<'src> {
+    type Item = Token<'src>;
+
+    fn next(&mut self) -> Option<Token<'src>> {
+        while self.scanned.is_some() || !self.at_end() {
+            if self.scanned.is_none() {
+                self.scan_token();
+            }
+            match self.scanned.take() {
+                Some(mut token) => {
+                    if self.lossless {
+                        token.leading_trivia = std::mem::take(&mut self.trivia);
+                        token.trailing_trivia = self.scan_trailing_trivia();
+                    }
+                    return Some(token);
+                }
+                None if self.lossless => {
+                    let mut trivia = std::mem::take(&mut self.trivia);
+                    self.push_trivia(&mut trivia);
+                    self.trivia = trivia;
+                }
+                None => {}
+            }
+        }
+
+        if self.finished {
+            return None;
+        }
+        self.finished = true;
+        Some(Token {
+            token_type: TokenType::Eof,
+            lexeme: Cow::Borrowed(""),
+            literal: LiteralType::Nil,
             line: self.line,
-        };
-        self.result.push(token);
+            column: self.column(self.current),
+            span: self.span(self.current, self.current),
+            leading_trivia: std::mem::take(&mut self.trivia),
+            trailing_trivia: vec![],
+        })
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Token {
-    token_type: TokenType,
-    lexeme: String,
-    literal: LiteralType,
-    line: usize,
+/// Separators have to sit between two digits, possibly next to further separators.
+fn separators_between_digits(text: &str, is_digit: impl Fn(char) -> bool) -> bool {
+    let chars: Vec<char> = text.chars().collect();
+    let digit_or_separator = |i: Option<usize>| {
+        i.and_then(|i| chars.get(i))
+            .is_some_and(|&c| c == '_' || is_digit(c))
+    };
+    (0..chars.len())
+        .filter(|&i| chars[i] == '_')
+        .all(|i| digit_or_separator(i.checked_sub(1)) && digit_or_separator(Some(i + 1)))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token<'src> {
+    pub token_type: TokenType,
+    /// Borrowed from the source, unless an identifier had to be normalized
+    pub lexeme: Cow<'src, str>,
+    pub literal: LiteralType<'src>,
+    /// 1-based line and column of the first character of the token
+    pub line: usize,
+    pub column: usize,
+    pub span: Span,
+    /// Only filled in by a lossless scanner, see `Scanner::lossless`
+    pub leading_trivia: Vec<Trivia<'src>>,
+    pub trailing_trivia: Vec<Trivia<'src>>,
+}
+
+/// Source text between tokens, that does not matter to the parser.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia<'src> {
+    pub kind: TriviaKind,
+    pub text: Cow<'src, str>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    Newline,
+    LineComment,
+    BlockComment,
+    Shebang,
+}
+
+impl TriviaKind {
+    fn of(text: &str) -> Self {
+        if text.starts_with("//") {
+            TriviaKind::LineComment
+        } else if text.starts_with("/*") {
+            TriviaKind::BlockComment
+        } else if text.starts_with("#!") {
+            TriviaKind::Shebang
+        } else if matches!(text, "\n" | "\r\n" | "\r") {
+            TriviaKind::Newline
+        } else {
+            TriviaKind::Whitespace
+        }
+    }
+}
+
+impl Trivia<'_> {
+    pub fn into_owned(self) -> Trivia<'static> {
+        Trivia {
+            kind: self.kind,
+            text: Cow::Owned(self.text.into_owned()),
+            span: self.span,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -266,6 +764,12 @@ pub enum TokenType {
     // Literals.
     Identifier,
     TString,
+    /// The part of a string literal up to a `${`, the embedded expression follows
+    Interpolation,
+    /// The part of a string literal from a `}` up to the next `${`
+    InterpolationMiddle,
+    /// The part of a string literal from the last `}` to its closing quote
+    InterpolationEnd,
     Number,
 
     // Keywords.
@@ -286,14 +790,81 @@ pub enum TokenType {
     Var,
     While,
 
+    /// Input that could not be scanned, the literal holds the diagnostic to report
+    Error,
     Eof,
 }
 
+impl Token<'_> {
+    /// Detaches the token from the source, e.g. to keep it in the AST.
+    pub fn into_owned(self) -> Token<'static> {
+        Token {
+            token_type: self.token_type,
+            lexeme: Cow::Owned(self.lexeme.into_owned()),
+            literal: self.literal.into_owned(),
+            line: self.line,
+            column: self.column,
+            span: self.span,
+            leading_trivia: self
+                .leading_trivia
+                .into_iter()
+                .map(Trivia::into_owned)
+                .collect(),
+            trailing_trivia: self
+                .trailing_trivia
+                .into_iter()
+                .map(Trivia::into_owned)
+                .collect(),
+        }
+    }
+
+    /// Whether this is an error token for a string or block comment that swallowed the rest of
+    /// the input because it was never closed.
+    pub fn is_unterminated(&self) -> bool {
+        match &self.literal {
+            LiteralType::Error(diagnostic) => {
+                self.token_type == Error && diagnostic.message.starts_with("Unterminated")
+            }
+            _ => false,
+        }
+    }
+
+    /// The token as it was written, including its trivia when scanned losslessly.
+    pub fn full_text(&self) -> String {
+        let leading = self
+            .leading_trivia
+            .iter()
+            .map(|trivia| trivia.text.as_ref());
+        let trailing = self
+            .trailing_trivia
+            .iter()
+            .map(|trivia| trivia.text.as_ref());
+        leading
+            .chain(std::iter::once(self.lexeme.as_ref()))
+            .chain(trailing)
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
-enum LiteralType {
+pub enum LiteralType<'src> {
     Nil,
-    StringLiteral(String),
-    NumberLiteral(f32),
+    StringLiteral(Cow<'src, str>),
+    NumberLiteral(f64),
+    Error(Box<Diagnostic>),
+}
+
+impl LiteralType<'_> {
+    pub fn into_owned(self) -> LiteralType<'static> {
+        match self {
+            LiteralType::Nil => LiteralType::Nil,
+            LiteralType::StringLiteral(value) => {
+                LiteralType::StringLiteral(Cow::Owned(value.into_owned()))
+            }
+            LiteralType::NumberLiteral(number) => LiteralType::NumberLiteral(number),
+            LiteralType::Error(diagnostic) => LiteralType::Error(diagnostic),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -310,11 +881,157 @@ mod tests {
             .collect()
     }
 
+    fn errors(input: &str) -> Vec<Diagnostic> {
+        Scanner::new(input)
+            .filter_map(|t| match t.literal {
+                LiteralType::Error(diagnostic) => Some(*diagnostic),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_yields_tokens_lazily() {
+        let mut scanner = Scanner::new("print a;");
+        assert_eq!(scanner.next().map(|t| t.token_type), Some(Print));
+        assert_eq!(scanner.next().map(|t| t.token_type), Some(Identifier));
+        assert_eq!(scanner.next().map(|t| t.token_type), Some(Semicolon));
+        assert_eq!(scanner.next().map(|t| t.token_type), Some(Eof));
+        assert_eq!(scanner.next(), None);
+        assert_eq!(scanner.next(), None);
+    }
+
+    #[test]
+    fn it_borrows_lexemes_and_literals_from_the_source() {
+        let source = String::from("var a = \"plain\" + \"esc\\n\" + e\u{301};");
+        let tokens: Vec<Token> = Scanner::new(&source).collect();
+        assert!(tokens.iter().all(|t| t.token_type != Error));
+
+        let borrowed = |value: &Cow<str>| matches!(value, Cow::Borrowed(_));
+        let owned: Vec<&str> = tokens
+            .iter()
+            .filter(|t| !borrowed(&t.lexeme))
+            .map(|t| t.lexeme.as_ref())
+            .collect();
+        assert_eq!(owned, vec!["\u{e9}"]);
+
+        let literals: Vec<bool> = tokens
+            .iter()
+            .filter_map(|t| match &t.literal {
+                LiteralType::StringLiteral(value) => Some(borrowed(value)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(literals, vec![true, false]);
+    }
+
+    fn reproduce(source: &str) -> String {
+        Scanner::new(source)
+            .lossless()
+            .map(|token| token.full_text())
+            .collect()
+    }
+
+    #[test]
+    fn it_reproduces_the_source_when_lossless() {
+        for source in [
+            include_str!("../comment.lox"),
+            include_str!("../sample.lox"),
+            "",
+            "  \n\t// only a comment",
+            "var a = 1;  /* trailing\n block */ // and more\n\n  print a ;  ",
+            "print \"a ${ \"b${c}\" } \\n\" + r\"\\q\";\n",
+            "var e\u{301} = 1e_ @@ § \"unterminated",
+            "a /* open",
+            "#!/usr/bin/env rlox\nprint 1;\n",
+            "\u{feff}var a;\r\n\x0cprint a; // crlf\r\r\n\"x\r\ny\"\r",
+        ] {
+            assert_eq!(reproduce(source), source);
+        }
+    }
+
+    #[test]
+    fn it_attaches_trivia_up_to_the_end_of_the_line_to_the_previous_token() {
+        let tokens: Vec<Token> = Scanner::new("a /* x */ // y\n  /* z */ b")
+            .lossless()
+            .collect();
+        let trivia = |trivia: &[Trivia]| -> Vec<(TriviaKind, String)> {
+            trivia
+                .iter()
+                .map(|t| (t.kind, t.text.to_string()))
+                .collect()
+        };
+        assert_eq!(
+            trivia(&tokens[0].trailing_trivia),
+            vec![
+                (TriviaKind::Whitespace, String::from(" ")),
+                (TriviaKind::BlockComment, String::from("/* x */")),
+                (TriviaKind::Whitespace, String::from(" ")),
+                (TriviaKind::LineComment, String::from("// y")),
+            ]
+        );
+        assert_eq!(
+            trivia(&tokens[1].leading_trivia),
+            vec![
+                (TriviaKind::Newline, String::from("\n")),
+                (TriviaKind::Whitespace, String::from("  ")),
+                (TriviaKind::BlockComment, String::from("/* z */")),
+                (TriviaKind::Whitespace, String::from(" ")),
+            ]
+        );
+        assert!(tokens[0].leading_trivia.is_empty());
+        assert!(tokens[2].leading_trivia.is_empty());
+    }
+
+    #[test]
+    fn it_keeps_errors_in_trailing_trivia_when_lossless() {
+        let tokens: Vec<Token> = Scanner::new("a /* open").lossless().collect();
+        let types: Vec<TokenType> = tokens.iter().map(|t| t.token_type.clone()).collect();
+        assert_eq!(types, vec![Identifier, Error, Eof]);
+        assert_eq!(tokens[1].lexeme, "/* open");
+        assert_eq!(tokens[0].trailing_trivia.len(), 1);
+    }
+
+    #[test]
+    fn it_keeps_no_trivia_by_default() {
+        let tokens = Scanner::new(" a // b\n").scan_tokens();
+        assert!(tokens
+            .iter()
+            .all(|t| t.leading_trivia.is_empty() && t.trailing_trivia.is_empty()));
+    }
+
     #[test]
     fn it_reads_single_line_comments() {
         assert_eq!(scan("// just a comment"), vec![Eof]);
     }
 
+    #[test]
+    fn it_reads_nested_block_comments() {
+        let tokens =
+            Scanner::new("/* outer /* inner\n */ still\n comment */ a /**/ / b").scan_tokens();
+        let lines: Vec<(TokenType, usize)> = tokens
+            .iter()
+            .map(|t| (t.token_type.clone(), t.line))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![(Identifier, 3), (Slash, 3), (Identifier, 3), (Eof, 3)]
+        );
+    }
+
+    #[test]
+    fn it_reports_unterminated_block_comments_where_they_open() {
+        let source = "a;\n  /* one /* two */\n\n";
+        let tokens = Scanner::new(source).scan_tokens();
+        assert_eq!(tokens.last().unwrap().line, 4);
+        let diagnostics = errors(source);
+        let errors: Vec<(usize, usize, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.line, d.column, d.message.as_str()))
+            .collect();
+        assert_eq!(errors, vec![(2, 3, "Unterminated block comment.")]);
+    }
+
     #[test]
     fn it_reads_bang_equals() {
         assert_eq!(
@@ -373,6 +1090,352 @@ mod tests {
         )
     }
 
+    fn number(input: &str) -> f64 {
+        match literal(input) {
+            LiteralType::NumberLiteral(number) => number,
+            literal => panic!("{:?} is not a number", literal),
+        }
+    }
+
+    #[test]
+    fn it_reads_numbers_as_doubles() {
+        assert_eq!(number("0.1"), 0.1);
+        assert_eq!(number("16777217"), 16777217.0);
+        assert_eq!(number("1e-9"), 1e-9);
+        assert_eq!(number("2.5E+3"), 2500.0);
+        assert_eq!(number("1_000_000"), 1_000_000.0);
+        assert_eq!(number("1_0.2_5e1_0"), 10.25e10);
+        assert_eq!(number("1.7e308"), 1.7e308);
+    }
+
+    #[test]
+    fn it_reads_hex_octal_and_binary_numbers() {
+        assert_eq!(number("0xff"), 255.0);
+        assert_eq!(number("0xDEAD_beef"), 3735928559.0);
+        assert_eq!(number("0o17"), 15.0);
+        assert_eq!(number("0b1010_1010"), 170.0);
+        assert_eq!(scan("0x10.y"), vec![Number, Dot, Identifier, Eof]);
+    }
+
+    #[test]
+    fn it_reports_malformed_numbers() {
+        for (input, message) in [
+            ("1e", "Expect digits in the exponent."),
+            ("1e-", "Expect digits in the exponent."),
+            ("1_", "Digit separators are only allowed between digits."),
+            ("1__", "Digit separators are only allowed between digits."),
+            ("1_.5", "Digit separators are only allowed between digits."),
+            ("1_e5", "Digit separators are only allowed between digits."),
+            ("0x", "Invalid hexadecimal literal."),
+            ("0xfg", "Invalid hexadecimal literal."),
+            ("0x_1", "Invalid hexadecimal literal."),
+            ("0o8", "Invalid octal literal."),
+            ("0b102", "Invalid binary literal."),
+            ("0x1_0000_0000_0000_0000", "Invalid hexadecimal literal."),
+            ("1e400", "Number literal is too large."),
+            ("1_000e3_08", "Number literal is too large."),
+        ] {
+            let diagnostics = errors(input);
+            let errors: Vec<(Span, &str)> = diagnostics
+                .iter()
+                .map(|d| (d.span, d.message.as_str()))
+                .collect();
+            assert_eq!(
+                errors,
+                vec![(Span::new(0, input.len()), message)],
+                "{}",
+                input
+            );
+            assert_eq!(scan(input), vec![Error, Eof], "{}", input);
+        }
+    }
+
+    #[test]
+    fn it_records_spans_lines_and_columns() {
+        let tokens = Scanner::new("var a =\n  \"x\ny\" ;").scan_tokens();
+        let positions: Vec<(TokenType, Span, usize, usize)> = tokens
+            .iter()
+            .map(|t| (t.token_type.clone(), t.span, t.line, t.column))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                (Var, Span::new(0, 3), 1, 1),
+                (Identifier, Span::new(4, 5), 1, 5),
+                (Equal, Span::new(6, 7), 1, 7),
+                (TString, Span::new(10, 15), 2, 3),
+                (Semicolon, Span::new(16, 17), 3, 4),
+                (Eof, Span::new(17, 17), 3, 5),
+            ]
+        );
+    }
+
+    fn lines_and_columns(input: &str) -> Vec<(usize, usize)> {
+        Scanner::new(input).map(|t| (t.line, t.column)).collect()
+    }
+
+    #[test]
+    fn it_counts_lines_for_every_newline_style() {
+        let expected = vec![(1, 1), (2, 1), (3, 1), (3, 2)];
+        assert_eq!(lines_and_columns("a\nb\nc"), expected);
+        assert_eq!(lines_and_columns("a\r\nb\r\nc"), expected);
+        assert_eq!(lines_and_columns("a\rb\rc"), expected);
+        assert_eq!(
+            lines_and_columns("a\r\rb\nc"),
+            vec![(1, 1), (3, 1), (4, 1), (4, 2)]
+        );
+        assert_eq!(
+            lines_and_columns("/* \r\n */ \"\r\\\r\" a // \r b"),
+            vec![(2, 5), (4, 3), (5, 2), (5, 3)]
+        );
+    }
+
+    #[test]
+    fn it_counts_columns_along_long_lines() {
+        let source = "\"ä\"+".repeat(1000);
+        let columns: Vec<usize> = Scanner::new(&source).map(|t| t.column).collect();
+        assert_eq!(columns.len(), 2001);
+        assert!(columns
+            .iter()
+            .enumerate()
+            .all(|(i, &column)| column == 1 + i / 2 * 4 + i % 2 * 3));
+
+        let diagnostics = errors("\"ä\" + \"ö\\q\"");
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (1, 9));
+    }
+
+    #[test]
+    fn it_skips_form_feeds_as_whitespace() {
+        assert_eq!(scan("a\x0cb"), vec![Identifier, Identifier, Eof]);
+        assert_eq!(lines_and_columns("a\x0c\nb"), vec![(1, 1), (2, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn it_skips_a_leading_byte_order_mark() {
+        let tokens = Scanner::new("\u{feff}var a;").scan_tokens();
+        assert_eq!(tokens[0].token_type, Var);
+        assert_eq!((tokens[0].line, tokens[0].column), (1, 1));
+        assert_eq!(tokens[0].span, Span::new(3, 6));
+        assert_eq!(scan("a \u{feff}"), vec![Identifier, Error, Eof]);
+    }
+
+    #[test]
+    fn it_skips_a_shebang_line_at_the_start() {
+        let tokens = Scanner::new("#!/usr/bin/env rlox\nprint 1;").scan_tokens();
+        assert_eq!(tokens[0].token_type, Print);
+        assert_eq!((tokens[0].line, tokens[0].column), (2, 1));
+        assert_eq!(scan("\u{feff}#!rlox\r\nprint"), vec![Print, Eof]);
+
+        let tokens: Vec<Token> = Scanner::new("#!rlox\nprint").lossless().collect();
+        let kinds: Vec<TriviaKind> = tokens[0].leading_trivia.iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, vec![TriviaKind::Shebang, TriviaKind::Newline]);
+    }
+
+    #[test]
+    fn it_rejects_hashes_anywhere_else() {
+        assert_eq!(scan(" #!rlox"), vec![Error, Bang, Identifier, Eof]);
+        assert_eq!(scan("\n#!rlox"), vec![Error, Bang, Identifier, Eof]);
+        assert_eq!(
+            scan("print #!rlox"),
+            vec![Print, Error, Bang, Identifier, Eof]
+        );
+        assert_eq!(scan("#print"), vec![Error, Print, Eof]);
+    }
+
+    #[test]
+    fn it_produces_error_tokens() {
+        let source = "var a = @;\n\"open";
+        assert_eq!(
+            scan(source),
+            vec![Var, Identifier, Equal, Error, Semicolon, Error, Eof]
+        );
+
+        let diagnostics = errors(source);
+
+        let messages: Vec<(usize, usize, Span, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.line, d.column, d.span, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (1, 9, Span::new(8, 9), "Unexpected character: @"),
+                (2, 1, Span::new(11, 16), "Unterminated string.")
+            ]
+        );
+    }
+
+    fn literal(input: &str) -> LiteralType<'static> {
+        Scanner::new(input).scan_tokens()[0].literal.clone()
+    }
+
+    fn string(value: &str) -> LiteralType<'_> {
+        LiteralType::StringLiteral(Cow::from(value))
+    }
+
+    #[test]
+    fn it_reads_escape_sequences() {
+        assert_eq!(
+            literal(r#""a\tb\nc\r\\ \"q\" \0""#),
+            string("a\tb\nc\r\\ \"q\" \0")
+        );
+        assert_eq!(literal(r#""\u{e4}\u{1F980}""#), string("ä🦀"));
+    }
+
+    #[test]
+    fn it_reports_invalid_escapes_at_the_escape() {
+        let source = "\"ok\";\n\"a \\q \\u{110000}\";\n\"\\u{}\" \"\\u41\"";
+        assert_eq!(
+            scan(source),
+            vec![TString, Semicolon, TString, Semicolon, TString, TString, Eof]
+        );
+
+        // only the first bad escape of a string is reported
+        let diagnostics = errors(source);
+        let errors: Vec<(Span, usize, usize, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.span, d.line, d.column, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (Span::new(9, 11), 2, 4, "Invalid escape sequence."),
+                (Span::new(26, 30), 3, 2, "Invalid unicode escape."),
+                (Span::new(33, 35), 3, 9, "Invalid unicode escape."),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_merges_runs_of_unexpected_characters() {
+        let source = "a @#§ b | ~";
+        assert_eq!(
+            scan(source),
+            vec![Identifier, Error, Identifier, Error, Error, Eof]
+        );
+        let errors: Vec<(Span, String)> = errors(source)
+            .into_iter()
+            .map(|d| (d.span, d.message))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (Span::new(2, 6), String::from("Unexpected characters: @#§")),
+                (Span::new(9, 10), String::from("Unexpected character: |")),
+                (Span::new(11, 12), String::from("Unexpected character: ~")),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_reads_raw_strings_without_escapes() {
+        assert_eq!(
+            literal(r#"r"C:\new\table\u{41}""#),
+            string(r"C:\new\table\u{41}")
+        );
+        assert_eq!(
+            scan(r#"r "x" r"y""#),
+            vec![Identifier, TString, TString, Eof]
+        );
+    }
+
+    #[test]
+    fn it_splits_interpolated_strings_into_parts() {
+        let tokens = Scanner::new(r#""a ${x} b ${ {1} + "c${y}d" } e""#).scan_tokens();
+        let parts: Vec<(TokenType, LiteralType)> = tokens
+            .iter()
+            .map(|t| (t.token_type.clone(), t.literal.clone()))
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                (Interpolation, string("a ")),
+                (Identifier, LiteralType::Nil),
+                (InterpolationMiddle, string(" b ")),
+                (LeftBrace, LiteralType::Nil),
+                (Number, LiteralType::NumberLiteral(1.0)),
+                (RightBrace, LiteralType::Nil),
+                (Plus, LiteralType::Nil),
+                (Interpolation, string("c")),
+                (Identifier, LiteralType::Nil),
+                (InterpolationEnd, string("d")),
+                (InterpolationEnd, string(" e")),
+                (Eof, LiteralType::Nil),
+            ]
+        );
+        assert_eq!(tokens[2].lexeme, "} b ${");
+        assert_eq!(literal(r#""\${z}""#), string("${z}"));
+    }
+
+    #[test]
+    fn it_reads_multi_byte_string_literals() {
+        let tokens = Scanner::new("\"grüße 🦀\" x").scan_tokens();
+        assert_eq!(tokens[0].lexeme, "\"grüße 🦀\"");
+        assert_eq!(
+            tokens[0].literal,
+            LiteralType::StringLiteral(Cow::from("grüße 🦀"))
+        );
+        assert_eq!(tokens[0].span, Span::new(0, 14));
+        assert_eq!((tokens[1].span, tokens[1].column), (Span::new(15, 16), 11));
+    }
+
+    #[test]
+    fn it_skips_emoji_in_comments() {
+        assert_eq!(
+            scan("// 🦀 crabs ✓\nprint 1; // 😀"),
+            vec![Print, Number, Semicolon, Eof]
+        );
+    }
+
+    #[test]
+    fn it_handles_non_ascii_content_at_the_end_of_the_input() {
+        let tokens = Scanner::new("a §").scan_tokens();
+        assert_eq!(tokens.last().unwrap().span, Span::new(4, 4));
+        let diagnostics = errors("a §");
+        let found: Vec<(Span, usize, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.span, d.column, d.message.as_str()))
+            .collect();
+        assert_eq!(found, vec![(Span::new(2, 4), 3, "Unexpected character: §")]);
+
+        let spans: Vec<Span> = errors("\"straße").iter().map(|d| d.span).collect();
+        assert_eq!(spans, vec![Span::new(0, 8)]);
+    }
+
+    #[test]
+    fn it_reads_unicode_identifiers() {
+        let tokens = Scanner::new("var größe = 名前_2;").scan_tokens();
+        let lexemes: Vec<(TokenType, &str)> = tokens
+            .iter()
+            .map(|t| (t.token_type.clone(), t.lexeme.as_ref()))
+            .collect();
+        assert_eq!(
+            lexemes,
+            vec![
+                (Var, "var"),
+                (Identifier, "größe"),
+                (Equal, "="),
+                (Identifier, "名前_2"),
+                (Semicolon, ";"),
+                (Eof, "")
+            ]
+        );
+    }
+
+    #[test]
+    fn it_normalizes_identifiers_to_nfc() {
+        let composed = Scanner::new("\u{e9}t\u{e9}").scan_tokens();
+        let decomposed = Scanner::new("e\u{301}te\u{301}").scan_tokens();
+        assert_eq!(composed[0].lexeme, decomposed[0].lexeme);
+        assert_eq!(decomposed[0].span, Span::new(0, 7));
+    }
+
+    #[test]
+    fn it_matches_keywords_exactly() {
+        assert_eq!(scan("vär ｖａｒ"), vec![Identifier, Identifier, Eof]);
+        assert_eq!(scan("🦀"), vec![Error, Eof]);
+    }
+
     #[test]
     fn it_reads_a_boolean_variable_definition() {
         assert_eq!(
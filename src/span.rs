@@ -0,0 +1,171 @@
+/// Tells apart the sources evaluated by one `Lox` session, e.g. the lines entered into the
+/// REPL. Functions defined by one source can fail while another one runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct SourceId(pub usize);
+
+/// A range of byte offsets into the source `source`, `end` is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub source: SourceId,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span {
+            start,
+            end,
+            source: SourceId::default(),
+        }
+    }
+
+    pub fn in_source(self, source: SourceId) -> Self {
+        Span { source, ..self }
+    }
+}
+
+/// Maps byte offsets back to 1-based line and column numbers, columns count characters.
+/// Lines end in `\n`, `\r\n` or a lone `\r`, like they do for the scanner.
+pub struct LineIndex<'a> {
+    source: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let bytes = source.as_bytes();
+        let line_breaks = bytes.iter().enumerate().filter(|&(offset, &byte)| {
+            byte == b'\n' || (byte == b'\r' && bytes.get(offset + 1) != Some(&b'\n'))
+        });
+        let line_starts = std::iter::once(0)
+            .chain(line_breaks.map(|(offset, _)| offset + 1))
+            .collect();
+        LineIndex {
+            source,
+            line_starts,
+        }
+    }
+
+    /// Offsets past the end of the source map to its end.
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let offset = self.floor_char_boundary(offset);
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        };
+        let line_start = self.line_starts[line];
+        let text = &self.source[line_start..offset];
+        // the scanner doesn't count a byte order mark as a column either
+        let text = text
+            .strip_prefix('\u{feff}')
+            .filter(|_| line == 0)
+            .unwrap_or(text);
+        (line + 1, text.chars().count() + 1)
+    }
+
+    /// The text of the 1-based `line`, without its line break, empty for lines past the end.
+    pub fn line_text(&self, line: usize) -> &'a str {
+        let Some(&start) = self.line_starts.get(line.wrapping_sub(1)) else {
+            return "";
+        };
+        let end = self
+            .line_starts
+            .get(line)
+            .copied()
+            .unwrap_or(self.source.len());
+        let text = &self.source[start..end];
+        let text = if line == 1 {
+            text.strip_prefix('\u{feff}').unwrap_or(text)
+        } else {
+            text
+        };
+        text.strip_suffix("\r\n")
+            .or_else(|| text.strip_suffix(['\n', '\r']))
+            .unwrap_or(text)
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Whether `span` fits the source, starting and ending on character boundaries.
+    pub fn contains(&self, span: Span) -> bool {
+        span.start <= span.end
+            && self.source.is_char_boundary(span.start)
+            && self.source.is_char_boundary(span.end)
+    }
+
+    fn floor_char_boundary(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_maps_offsets_to_lines_and_columns() {
+        let index = LineIndex::new("var a;\nprint a;\n\nx");
+        assert_eq!(index.line_column(0), (1, 1));
+        assert_eq!(index.line_column(4), (1, 5));
+        assert_eq!(index.line_column(6), (1, 7));
+        assert_eq!(index.line_column(7), (2, 1));
+        assert_eq!(index.line_column(13), (2, 7));
+        assert_eq!(index.line_column(16), (3, 1));
+        assert_eq!(index.line_column(17), (4, 1));
+        assert_eq!(index.line_column(18), (4, 2));
+    }
+
+    #[test]
+    fn it_counts_columns_in_characters() {
+        let index = LineIndex::new("\"äöü\" x");
+        assert_eq!(index.line_column(9), (1, 7));
+    }
+
+    #[test]
+    fn it_handles_every_line_ending() {
+        let index = LineIndex::new("a\r\nb\rc\nd");
+        assert_eq!(index.line_count(), 4);
+        assert_eq!(index.line_column(3), (2, 1));
+        assert_eq!(index.line_column(5), (3, 1));
+        assert_eq!(index.line_column(7), (4, 1));
+        let lines: Vec<&str> = (1..=4).map(|line| index.line_text(line)).collect();
+        assert_eq!(lines, vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn it_skips_a_byte_order_mark() {
+        let index = LineIndex::new("\u{feff}var a;");
+        assert_eq!(index.line_column(3), (1, 1));
+        assert_eq!(index.line_column(7), (1, 5));
+        assert_eq!(index.line_text(1), "var a;");
+    }
+
+    #[test]
+    fn it_clamps_offsets_and_lines_outside_the_source() {
+        let index = LineIndex::new("f();");
+        assert_eq!(index.line_column(17), (1, 5));
+        assert_eq!(index.line_text(3), "");
+        assert!(!index.contains(Span::new(14, 17)));
+        assert!(index.contains(Span::new(0, 4)));
+
+        let index = LineIndex::new("ä");
+        assert_eq!(index.line_column(1), (1, 1));
+        assert!(!index.contains(Span::new(0, 1)));
+    }
+
+    #[test]
+    fn it_returns_line_text() {
+        let index = LineIndex::new("first\nsecond\n");
+        assert_eq!(index.line_count(), 3);
+        assert_eq!(index.line_text(1), "first");
+        assert_eq!(index.line_text(2), "second");
+        assert_eq!(index.line_text(3), "");
+    }
+}